@@ -1,36 +1,42 @@
+use crate::error::{ErrorCode, NeesyError};
 use crate::evaluator::Value;
 
-use std::io::{stdin, Read};
+use std::io::stdin;
 
 pub mod convert;
+pub mod map;
 pub mod math;
 
-pub fn read_line(values : Vec<Value>) -> Result<Value, String> {
+pub fn read_line(_values : Vec<Value>) -> Result<Value, NeesyError> {
     let mut buf = String::new();
 
-    stdin().read_line(&mut buf).unwrap();
+    if let Err(err) = stdin().read_line(&mut buf) {
+        return Err(NeesyError::runtime(ErrorCode::Io, err.to_string(), None));
+    }
 
-    buf = buf[..buf.len() - 1].to_string();
+    if buf.ends_with('\n') {
+        buf.pop();
+    }
 
     Ok(Value::Str(buf))
 }
 
-pub fn puts_num(values : Vec<Value>) -> Result<Value, String> {
-    if let Value::Number(num) = &values[0] {
-        println!("{}", *num);
+pub fn puts_num(values : Vec<Value>) -> Result<Value, NeesyError> {
+    if let Value::Int(_) | Value::BigInt(_) | Value::Decimal(_) | Value::Number(_) = &values[0] {
+        println!("{}", values[0]);
 
         Ok(Value::Void)
     } else {
-        Err("Invalid type parameter".to_owned())
+        Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Invalid type parameter".to_owned(), None))
     }
 }
 
-pub fn puts_str(values : Vec<Value>) -> Result<Value, String> {
+pub fn puts_str(values : Vec<Value>) -> Result<Value, NeesyError> {
     if let Value::Str(num) = &values[0] {
         println!("{}", num);
 
         Ok(Value::Void)
     } else {
-        Err("Invalid type parameter".to_owned())
+        Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Invalid type parameter".to_owned(), None))
     }
 }
\ No newline at end of file
@@ -1,22 +1,146 @@
-use crate::evaluator::Value;
+use crate::error::{ErrorCode, NeesyError};
+use crate::evaluator::{self, Value};
 
-pub fn to_string(values: Vec<Value>) -> Result<Value, String> {
-    match &values[0] {
-        Value::Number(num) => Ok(Value::Str(format!("{}", *num))),
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+
+pub fn to_string(values: Vec<Value>) -> Result<Value, NeesyError> {
+    stringify(&values[0]).map(Value::Str)
+}
+
+// The text `to_string` gives for `value`, also used by interpolated strings.
+pub fn stringify(value: &Value) -> Result<String, NeesyError> {
+    match value {
+        Value::Int(_) | Value::BigInt(_) | Value::Decimal(_) | Value::Number(_) => Ok(value.to_string()),
         Value::Bool(b) => 
-            Ok(Value::Str(if *b { "true".to_string() } else { "false".to_string() })),
+            Ok(if *b { "true".to_string() } else { "false".to_string() }),
         
-        Value::Str(string) => Ok(Value::Str(string.clone())),
+        Value::Str(string) => Ok(string.clone()),
+
+        Value::Array(_) | Value::Map(_) | Value::Range(_) | Value::Struct(..) => Ok(value.to_string()),
+
+        _ => Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Bad argument.".to_string(), None))
+    }
+}
+
+pub fn to_number(values: Vec<Value>) -> Result<Value, NeesyError> {
+    match &values[0] {
+        Value::Str(s) => match s.parse::<i64>() {
+            Ok(n) => Ok(Value::Int(n)),
+            Err(_) => Ok(Value::Number(s.parse::<f64>().unwrap_or(0.0))),
+        },
+
+        _ => Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Bad argument.".to_string(), None))
+    }
+}
+
+// Truncates floats towards zero.
+pub fn int(values: Vec<Value>) -> Result<Value, NeesyError> {
+    match &values[0] {
+        Value::Int(n) => Ok(Value::Int(*n)),
+
+        // `i64::MAX as f64` rounds up to 2^63, which doesn't fit.
+        Value::Number(n) if n.is_finite() && *n >= i64::MIN as f64 && *n < i64::MAX as f64 => {
+            Ok(Value::Int(*n as i64))
+        }
+
+        Value::Number(n) => Err(NeesyError::runtime(ErrorCode::NumericOverflow, format!("{:?} doesn't fit in an integer", n), None)),
+
+        Value::BigInt(n) => match n.to_i64() {
+            Some(n) => Ok(Value::Int(n)),
+            None => Err(NeesyError::runtime(ErrorCode::NumericOverflow, format!("{} doesn't fit in an integer", n), None)),
+        },
+
+        Value::Decimal(n) => match n.trunc().to_i64() {
+            Some(n) => Ok(Value::Int(n)),
+            None => Err(NeesyError::runtime(ErrorCode::NumericOverflow, format!("{} doesn't fit in an integer", n), None)),
+        },
+
+        Value::Str(s) => match s.trim().parse::<i64>() {
+            Ok(n) => Ok(Value::Int(n)),
+            Err(_) => Err(NeesyError::runtime(ErrorCode::TypeMismatch, format!("{:?} is not an integer", s), None)),
+        },
 
-        _ => Err("Bad argument.".to_string())
+        _ => Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Bad argument.".to_string(), None))
     }
 }
 
-pub fn to_number(values: Vec<Value>) -> Result<Value, String> {
+pub fn float(values: Vec<Value>) -> Result<Value, NeesyError> {
     match &values[0] {
-        Value::Str(s) => Ok(
-            Value::Number(s.parse::<f64>().unwrap_or(0.0).to_owned())),
+        Value::Int(n) => Ok(Value::Number(*n as f64)),
+        Value::BigInt(n) => Ok(Value::Number(n.to_f64().unwrap_or(f64::NAN))),
+        Value::Decimal(n) => Ok(Value::Number(n.to_f64().unwrap_or(f64::NAN))),
+        Value::Number(n) => Ok(Value::Number(*n)),
 
-        _ => Err("Bad argument.".to_string())
+        Value::Str(s) => match s.trim().parse::<f64>() {
+            Ok(n) => Ok(Value::Number(n)),
+            Err(_) => Err(NeesyError::runtime(ErrorCode::TypeMismatch, format!("{:?} is not a number", s), None)),
+        },
+
+        _ => Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Bad argument.".to_string(), None))
     }
 }
+
+// Truncates decimals and floats towards zero.
+pub fn bigint(values: Vec<Value>) -> Result<Value, NeesyError> {
+    match &values[0] {
+        Value::Int(n) => Ok(Value::BigInt(BigInt::from(*n))),
+        Value::BigInt(n) => Ok(Value::BigInt(n.clone())),
+        Value::Decimal(n) => Ok(Value::BigInt(BigInt::from(n.trunc().mantissa()))),
+
+        Value::Number(n) => match BigInt::from_f64(n.trunc()) {
+            Some(n) => Ok(Value::BigInt(n)),
+            None => Err(NeesyError::runtime(ErrorCode::NumericOverflow, format!("{:?} is not a finite number", n), None)),
+        },
+
+        Value::Str(s) => match BigInt::from_str(s.trim()) {
+            Ok(n) => Ok(Value::BigInt(n)),
+            Err(_) => Err(NeesyError::runtime(ErrorCode::TypeMismatch, format!("{:?} is not an integer", s), None)),
+        },
+
+        _ => Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Bad argument.".to_string(), None))
+    }
+}
+
+// Floats are converted to the closest decimal with at most 28 digits, so
+// `[decimal 0.1]` is exactly `0.1d`.
+pub fn decimal(values: Vec<Value>) -> Result<Value, NeesyError> {
+    let overflow = |value: &Value| NeesyError::runtime(ErrorCode::NumericOverflow, format!("{} doesn't fit in a decimal", value), None);
+
+    match &values[0] {
+        Value::Int(n) => Ok(Value::Decimal(Decimal::from(*n))),
+        Value::Decimal(n) => Ok(Value::Decimal(*n)),
+
+        Value::BigInt(n) => n.to_i128()
+            .and_then(Decimal::from_i128)
+            .map(Value::Decimal)
+            .ok_or_else(|| overflow(&values[0])),
+
+        Value::Number(n) => Decimal::from_f64(*n)
+            .map(Value::Decimal)
+            .ok_or_else(|| overflow(&values[0])),
+
+        Value::Str(s) => {
+            let s = s.trim();
+
+            match Decimal::from_str_exact(s).or_else(|_| Decimal::from_scientific(s)) {
+                Ok(n) => Ok(Value::Decimal(n)),
+                Err(_) => Err(NeesyError::runtime(ErrorCode::TypeMismatch, format!("{:?} is not a decimal number", s), None)),
+            }
+        },
+
+        _ => Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Bad argument.".to_string(), None))
+    }
+}
+
+// The items a `for` loop over the argument goes through, as an array.
+pub fn to_array(values: Vec<Value>) -> Result<Value, NeesyError> {
+    let items = evaluator::iterate(values[0].clone())?.collect();
+
+    Ok(Value::Array(Rc::new(RefCell::new(items))))
+}
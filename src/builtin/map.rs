@@ -0,0 +1,45 @@
+use crate::error::{ErrorCode, NeesyError};
+use crate::evaluator::{MapKey, Value};
+
+use linked_hash_map::LinkedHashMap;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn expect_map(value: &Value) -> Result<&Rc<RefCell<LinkedHashMap<MapKey, Value>>>, NeesyError> {
+    match value {
+        Value::Map(map) => Ok(map),
+        _ => Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Invalid type parameter".to_owned(), None))
+    }
+}
+
+fn array(values: Vec<Value>) -> Value {
+    Value::Array(Rc::new(RefCell::new(values)))
+}
+
+// The keys of a map, in insertion order.
+pub fn keys(values: Vec<Value>) -> Result<Value, NeesyError> {
+    let map = expect_map(&values[0])?.borrow();
+
+    Ok(array(map.keys().map(MapKey::to_value).collect()))
+}
+
+// The values of a map, in the insertion order of their keys.
+pub fn values(values: Vec<Value>) -> Result<Value, NeesyError> {
+    let map = expect_map(&values[0])?.borrow();
+
+    Ok(array(map.values().cloned().collect()))
+}
+
+pub fn has(values: Vec<Value>) -> Result<Value, NeesyError> {
+    let map = expect_map(&values[0])?.borrow();
+
+    Ok(Value::Bool(map.contains_key(&MapKey::new(&values[1])?)))
+}
+
+// Removes a key from a map, returning its value, or void if it wasn't there.
+pub fn remove(values: Vec<Value>) -> Result<Value, NeesyError> {
+    let mut map = expect_map(&values[0])?.borrow_mut();
+
+    Ok(map.remove(&MapKey::new(&values[1])?).unwrap_or(Value::Void))
+}
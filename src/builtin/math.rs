@@ -1,20 +1,34 @@
-use crate::evaluator::Value;
+use crate::error::{ErrorCode, NeesyError};
+use crate::evaluator::{Range, Value};
 
-pub fn pow(values: Vec<Value>) -> Result<Value, String> {
+pub fn pow(values: Vec<Value>) -> Result<Value, NeesyError> {
     let x : f64;
     let y : f64;
 
     if let Value::Number(num) = &values[0] {
         x = *num;
+    } else if let Value::Int(num) = &values[0] {
+        x = *num as f64;
     } else {
-        return Err("Invalid type parameter".to_owned());
+        return Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Invalid type parameter".to_owned(), None));
     }
 
     if let Value::Number(num) = &values[1] {
         y = *num;
+    } else if let Value::Int(num) = &values[1] {
+        y = *num as f64;
     } else {
-        return Err("Invalid type parameter".to_owned());
+        return Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Invalid type parameter".to_owned(), None));
     }
 
     Ok(Value::Number(x.powf(y)))
 }
+
+// The integers from the first argument up to, but excluding, the second one.
+pub fn range(values: Vec<Value>) -> Result<Value, NeesyError> {
+    match (&values[0], &values[1]) {
+        (Value::Int(start), Value::Int(end)) => Ok(Value::Range(Range::new(*start, *end, 1, false))),
+
+        _ => Err(NeesyError::runtime(ErrorCode::TypeMismatch, "Invalid type parameter".to_owned(), None))
+    }
+}
@@ -0,0 +1,203 @@
+use crate::error::{ErrorCode, NeesyError};
+use crate::span::Span;
+
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<ErrorCode>,
+    pub message: String,
+    pub span: Option<Span>,
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: String) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message,
+            span: None,
+            label: None,
+            notes: vec![],
+            help: None,
+        }
+    }
+
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, label: String) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn with_help(mut self, help: String) -> Self {
+        self.help = Some(help);
+        self
+    }
+
+    // Renders the diagnostic the way rustc does, quoting the line of `source`
+    // the span points at. `source` must be the text of the span's file; the
+    // quote is left out when it's `None`.
+    pub fn render(&self, source: Option<&str>, color: bool) -> String {
+        let paint = |style: &str, text: &str| -> String {
+            if color { format!("{}{}{}", style, text, RESET) } else { text.to_string() }
+        };
+
+        let mut out = String::new();
+
+        let header = match self.code {
+            Some(code) => format!("{}[{}]", self.severity.as_str(), code),
+            None => self.severity.as_str().to_string(),
+        };
+
+        let _ = writeln!(out, "{}{}", paint(self.severity.color(), &header), paint(BOLD, &format!(": {}", self.message)));
+
+        let line = self.span.as_ref().zip(source).and_then(|(span, source)| {
+            source.lines().nth(span.start.line - 1).map(|line| (span, line))
+        });
+
+        let gutter_width = match &line {
+            Some((span, _)) => span.start.line.to_string().len(),
+            None => 1,
+        };
+        let gutter = " ".repeat(gutter_width);
+
+        if let Some(span) = &self.span {
+            let _ = writeln!(out, "{}{} {}", gutter, paint(BLUE, "-->"), span);
+        }
+
+        if let Some((span, text)) = line {
+            let _ = writeln!(out, "{} {}", gutter, paint(BLUE, "|"));
+            let _ = writeln!(out, "{} {}", paint(BLUE, &format!("{} |", span.start.line)), text);
+
+            // Reuse the tabs of the quoted line so the carets stay aligned.
+            let padding: String = text.chars()
+                .take(span.start.column - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+
+            let line_length = text.chars().count() + 1;
+            let end_column = if span.end.line == span.start.line { span.end.column } else { line_length };
+            let width = end_column.saturating_sub(span.start.column).max(1);
+
+            let mut underline = "^".repeat(width);
+            if let Some(label) = &self.label {
+                underline.push(' ');
+                underline.push_str(label);
+            }
+
+            let _ = writeln!(out, "{} {} {}", gutter, paint(BLUE, "|"), padding + &paint(self.severity.color(), &underline));
+        }
+
+        if !self.notes.is_empty() || self.help.is_some() {
+            let _ = writeln!(out, "{} {}", gutter, paint(BLUE, "|"));
+        }
+
+        for note in &self.notes {
+            let _ = writeln!(out, "{} {} {}", gutter, paint(BLUE, "="), paint(BOLD, "note:") + " " + note);
+        }
+
+        if let Some(help) = &self.help {
+            let _ = writeln!(out, "{} {} {}", gutter, paint(BLUE, "="), paint(BOLD, "help:") + " " + help);
+        }
+
+        out
+    }
+}
+
+impl From<&NeesyError> for Diagnostic {
+    fn from(err: &NeesyError) -> Self {
+        let mut diagnostic = Diagnostic::new(Severity::Error, err.message().to_string())
+            .with_code(err.code());
+
+        if let Some(span) = err.span() {
+            diagnostic = diagnostic.with_span(span.clone());
+        }
+
+        let (label, help) = match err.code() {
+            ErrorCode::UnknownOperator        => ("unknown operator", None),
+            ErrorCode::InvalidNumber          => ("invalid number literal", None),
+            ErrorCode::UnterminatedString     => ("string starts here", Some("add a closing `\"`")),
+            ErrorCode::UnterminatedComment    => ("comment starts here", Some("add a closing `*/`")),
+            ErrorCode::InvalidEscape          => ("invalid escape sequence", Some("use `\\\\` for a literal backslash, or a raw string like `r\"...\"`")),
+
+            ErrorCode::UnexpectedToken        => ("unexpected token", None),
+            ErrorCode::UnexpectedEnd          => ("input ends here", None),
+            ErrorCode::ExpectedExpression     => ("expected an expression", None),
+            ErrorCode::InvalidAssignment      => ("cannot be assigned to", Some("only identifiers, fields and indexes can be assigned to")),
+            ErrorCode::DuplicateParameter     => ("parameter already declared", None),
+            ErrorCode::ReturnOutsideFunction  => ("not inside a function", None),
+            ErrorCode::LoopControlOutsideLoop => ("not inside a loop", None),
+            ErrorCode::DuplicateField         => ("field already declared", None),
+
+            ErrorCode::UndefinedVariable      => ("not found in this scope", Some("variables must be assigned with `<-` before use")),
+            ErrorCode::VoidAssignment         => ("this evaluates to void", None),
+            ErrorCode::BuiltinAssignment      => ("builtin functions are read-only", None),
+            ErrorCode::NotCallable            => ("not a function", None),
+            ErrorCode::WrongArgumentCount     => ("wrong number of arguments", None),
+            ErrorCode::InvalidOperands        => ("invalid operands", None),
+            ErrorCode::TypeMismatch           => ("wrong type", None),
+            ErrorCode::IndexOutOfBounds       => ("index out of bounds", None),
+            ErrorCode::Io                     => ("I/O failure", None),
+            ErrorCode::NumericOverflow        => ("numeric overflow", Some("big integers like `1n` have no size limit")),
+            ErrorCode::DivisionByZero         => ("division by zero", None),
+            ErrorCode::KeyNotFound            => ("key not found", Some("use `[has map key]` to check whether a key is there")),
+            ErrorCode::UnknownField           => ("unknown field", None),
+            ErrorCode::InvalidRange           => ("invalid range", None),
+            ErrorCode::NoMatchingArm          => ("no arm matches this value", Some("add a `_ => ...` arm to handle any other value")),
+        };
+
+        diagnostic = diagnostic.with_label(label.to_string());
+
+        if let Some(help) = help {
+            diagnostic = diagnostic.with_help(help.to_string());
+        }
+
+        diagnostic
+    }
+}
@@ -0,0 +1,164 @@
+use crate::span::Span;
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    // Lexer
+    UnknownOperator,
+    InvalidNumber,
+    UnterminatedString,
+    UnterminatedComment,
+    InvalidEscape,
+
+    // Parser
+    UnexpectedToken,
+    UnexpectedEnd,
+    ExpectedExpression,
+    InvalidAssignment,
+    DuplicateParameter,
+    ReturnOutsideFunction,
+    LoopControlOutsideLoop,
+    DuplicateField,
+
+    // Runtime
+    UndefinedVariable,
+    VoidAssignment,
+    BuiltinAssignment,
+    NotCallable,
+    WrongArgumentCount,
+    InvalidOperands,
+    TypeMismatch,
+    IndexOutOfBounds,
+    Io,
+    NumericOverflow,
+    DivisionByZero,
+    KeyNotFound,
+    UnknownField,
+    InvalidRange,
+    NoMatchingArm,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::UnknownOperator        => "E0101",
+            ErrorCode::InvalidNumber          => "E0102",
+            ErrorCode::UnterminatedString     => "E0103",
+            ErrorCode::UnterminatedComment    => "E0104",
+            ErrorCode::InvalidEscape          => "E0105",
+
+            ErrorCode::UnexpectedToken        => "E0201",
+            ErrorCode::UnexpectedEnd          => "E0202",
+            ErrorCode::ExpectedExpression     => "E0203",
+            ErrorCode::InvalidAssignment      => "E0204",
+            ErrorCode::DuplicateParameter     => "E0205",
+            ErrorCode::ReturnOutsideFunction  => "E0206",
+            ErrorCode::LoopControlOutsideLoop => "E0207",
+            ErrorCode::DuplicateField         => "E0208",
+
+            ErrorCode::UndefinedVariable      => "E0301",
+            ErrorCode::VoidAssignment         => "E0302",
+            ErrorCode::BuiltinAssignment      => "E0303",
+            ErrorCode::NotCallable            => "E0304",
+            ErrorCode::WrongArgumentCount     => "E0305",
+            ErrorCode::InvalidOperands        => "E0306",
+            ErrorCode::TypeMismatch           => "E0307",
+            ErrorCode::IndexOutOfBounds       => "E0308",
+            ErrorCode::Io                     => "E0309",
+            ErrorCode::NumericOverflow        => "E0310",
+            ErrorCode::DivisionByZero         => "E0311",
+            ErrorCode::KeyNotFound            => "E0312",
+            ErrorCode::UnknownField           => "E0313",
+            ErrorCode::InvalidRange           => "E0314",
+            ErrorCode::NoMatchingArm          => "E0315",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorInfo {
+    pub code: ErrorCode,
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NeesyError {
+    LexError(ErrorInfo),
+    ParseError(ErrorInfo),
+    RuntimeError(ErrorInfo),
+}
+
+impl NeesyError {
+    pub fn lex(code: ErrorCode, message: String, span: Option<Span>) -> Self {
+        NeesyError::LexError(ErrorInfo { code, message, span })
+    }
+
+    pub fn parse(code: ErrorCode, message: String, span: Option<Span>) -> Self {
+        NeesyError::ParseError(ErrorInfo { code, message, span })
+    }
+
+    pub fn runtime(code: ErrorCode, message: String, span: Option<Span>) -> Self {
+        NeesyError::RuntimeError(ErrorInfo { code, message, span })
+    }
+
+    pub fn info(&self) -> &ErrorInfo {
+        match self {
+            NeesyError::LexError(info) |
+            NeesyError::ParseError(info) |
+            NeesyError::RuntimeError(info) => info,
+        }
+    }
+
+    fn info_mut(&mut self) -> &mut ErrorInfo {
+        match self {
+            NeesyError::LexError(info) |
+            NeesyError::ParseError(info) |
+            NeesyError::RuntimeError(info) => info,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.info().code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.info().message
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        self.info().span.as_ref()
+    }
+
+    // Attaches `span` to errors raised without a location (e.g. by builtins),
+    // keeping the more precise one if it's already there.
+    pub fn with_span(mut self, span: &Span) -> Self {
+        let info = self.info_mut();
+
+        if info.span.is_none() {
+            info.span = Some(span.clone());
+        }
+
+        self
+    }
+}
+
+impl fmt::Display for NeesyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let info = self.info();
+
+        match &info.span {
+            Some(span) => write!(f, "[{}] {}: {}", info.code, span, info.message),
+            None => write!(f, "[{}] {}", info.code, info.message),
+        }
+    }
+}
+
+impl std::error::Error for NeesyError {}
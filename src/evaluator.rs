@@ -1,23 +1,147 @@
+use crate::builtin;
+use crate::error::NeesyError;
 use crate::parser::operators::*;
 use crate::parser::*;
+use crate::span::{Span, Spanned};
 
+use linked_hash_map::LinkedHashMap;
 use linked_hash_set::LinkedHashSet;
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
 use std::collections::{HashMap};
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::fmt;
 
 #[macro_use] mod errors;
 
 #[derive(Debug, Clone)]
 pub enum Value {
     Void,
+    Int(i64),
+    BigInt(BigInt),
+    Decimal(Decimal),
     Number(f64),
     Bool(bool),
     Str(String),
     Array(Rc<RefCell<Vec<Value>>>),
-    Function(Rc<LinkedHashSet<String>>, Rc<Vec<Expression>>),
-    BuiltinFunction(usize, fn(Vec<Value>) -> Result<Value, String>),
+    Map(Rc<RefCell<LinkedHashMap<MapKey, Value>>>),
+    Range(Range),
+    StructType(Rc<StructType>),
+    Struct(Rc<StructType>, Rc<RefCell<Vec<Value>>>),
+    Function(Rc<LinkedHashSet<String>>, Rc<Vec<Spanned<Expression>>>, Rc<RefCell<Environment>>),
+    BuiltinFunction(usize, fn(Vec<Value>) -> Result<Value, NeesyError>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_value(self, f, &mut Vec::new())
+    }
+}
+
+// Writes `value`, with `printing` holding the arrays, maps and structs it's
+// nested in. One that contains itself is written as `:...:`, `#{...}` or
+// `Name { ... }` the second time, instead of recursing forever.
+fn write_value(value: &Value, f: &mut fmt::Formatter, printing: &mut Vec<*const ()>) -> fmt::Result {
+    let pointer = match value {
+        Value::Array(arr) => Rc::as_ptr(arr) as *const (),
+        Value::Map(map) => Rc::as_ptr(map) as *const (),
+        Value::Struct(_, values) => Rc::as_ptr(values) as *const (),
+
+        _ => return write_scalar(value, f),
+    };
+
+    if printing.contains(&pointer) {
+        return match value {
+            Value::Array(_) => write!(f, ":...:"),
+            Value::Map(_) => write!(f, "#{{...}}"),
+            Value::Struct(struct_type, _) => write!(f, "{} {{ ... }}", struct_type.name),
+            _ => unreachable!(),
+        };
+    }
+
+    printing.push(pointer);
+
+    let result = match value {
+        Value::Array(arr) => {
+            write!(f, ":")?;
+
+            for (i, value) in arr.borrow().iter().enumerate() {
+                if i > 0 { write!(f, "; ")?; }
+
+                write_element(value, f, printing)?;
+            }
+
+            write!(f, ":")
+        }
+
+        Value::Map(map) => {
+            write!(f, "#{{")?;
+
+            for (i, (key, value)) in map.borrow().iter().enumerate() {
+                if i > 0 { write!(f, "; ")?; }
+
+                write!(f, "{} => ", key)?;
+                write_element(value, f, printing)?;
+            }
+
+            write!(f, "}}")
+        }
+
+        Value::Struct(struct_type, values) => {
+            write!(f, "{} {{ ", struct_type.name)?;
+
+            for (i, (field, value)) in struct_type.fields.iter().zip(values.borrow().iter()).enumerate() {
+                if i > 0 { write!(f, ", ")?; }
+
+                write!(f, "{}: ", field)?;
+                write_element(value, f, printing)?;
+            }
+
+            write!(f, " }}")
+        }
+
+        _ => unreachable!(),
+    };
+
+    printing.pop();
+
+    result
+}
+
+// Strings inside arrays, maps and structs are quoted.
+fn write_element(value: &Value, f: &mut fmt::Formatter, printing: &mut Vec<*const ()>) -> fmt::Result {
+    match value {
+        Value::Str(s) => write!(f, "{:?}", s),
+        _ => write_value(value, f, printing),
+    }
+}
+
+fn write_scalar(value: &Value, f: &mut fmt::Formatter) -> fmt::Result {
+    match value {
+        Value::Void => write!(f, "void"),
+        Value::Int(n) => write!(f, "{}", n),
+        Value::BigInt(n) => write!(f, "{}", n),
+        Value::Decimal(n) => write!(f, "{}", n),
+        // Floats always show a decimal part (or an exponent), so they're
+        // told apart from integers.
+        Value::Number(n) => write!(f, "{:?}", n),
+        Value::Bool(b) => write!(f, "{}", b),
+        Value::Str(s) => write!(f, "{}", s),
+        Value::Range(range) => write!(f, "{}", range),
+        Value::StructType(struct_type) => write!(f, "<struct {}>", struct_type.name),
+
+        Value::Function(params, _, _) => {
+            let params: Vec<&str> = params.iter().map(|p| p.as_str()).collect();
+            write!(f, "|{}| {{ ... }}", params.join(", "))
+        }
+
+        Value::BuiltinFunction(..) => write!(f, "<builtin function>"),
+
+        Value::Array(_) | Value::Map(_) | Value::Struct(..) => unreachable!(),
+    }
 }
 
 mod basic;
@@ -25,250 +149,697 @@ mod basic;
 mod boolean;
 use boolean::*;
 
+mod environment;
+pub use environment::Environment;
+
+mod map;
+pub use map::MapKey;
+
+mod range;
+pub use range::Range;
+
+mod structs;
+pub use structs::StructType;
+
+// Ways out of an expression other than its value: errors, and control flow
+// statements travelling up to the construct they target.
+#[derive(Debug)]
+enum Unwind {
+    Error(NeesyError),
+    Return(Value, Span),
+    Break(Span),
+    Continue(Span),
+}
+
+impl From<NeesyError> for Unwind {
+    fn from(err: NeesyError) -> Self {
+        Unwind::Error(err)
+    }
+}
+
+// The items a `for` loop goes through: the elements of an array, the characters
+// of a string, the `:key; value:` entries of a map or the numbers of a range.
+// Arrays and maps are copied first, so the loop isn't affected by changes made
+// to them in its body.
+pub fn iterate(value: Value) -> Result<Box<dyn Iterator<Item = Value>>, NeesyError> {
+    match value {
+        Value::Array(arr) => Ok(Box::new(arr.borrow().clone().into_iter())),
+
+        Value::Str(s) => {
+            let chars: Vec<Value> = s.chars().map(|c| Value::Str(c.to_string())).collect();
+            Ok(Box::new(chars.into_iter()))
+        }
+
+        Value::Map(map) => {
+            let entries: Vec<Value> = map.borrow().iter()
+                .map(|(key, value)| Value::Array(Rc::new(RefCell::new(vec![key.to_value(), value.clone()]))))
+                .collect();
+
+            Ok(Box::new(entries.into_iter()))
+        }
+
+        Value::Range(range) => Ok(Box::new(range.iter().map(Value::Int))),
+
+        x => runtime_err!(TypeMismatch, None, format!("Cannot iterate over {:?}.", x)),
+    }
+}
+
+// How a called expression is referred to in error messages.
+fn callee_name(callee: &Expression) -> String {
+    match callee {
+        Expression::Id(name) => name.clone(),
+        Expression::FieldAccess(_, field) => field.clone(),
+
+        _ => "<anonymous>".to_string(),
+    }
+}
+
+// Checks that a range's start, end or step is an integer.
+fn range_bound(value: Value, span: &Span) -> Result<i64, NeesyError> {
+    match value {
+        Value::Int(n) => Ok(n),
+        _ => runtime_err!(TypeMismatch, Some(span.clone()), "Range bounds must be integers."),
+    }
+}
+
+// Checks that `index` is an integer pointing inside an array of length `len`.
+fn array_index(index: &Value, len: usize, span: &Span) -> Result<usize, NeesyError> {
+    match index {
+        Value::Int(n) => match usize::try_from(*n) {
+            Ok(index) if index < len => Ok(index),
+            _ => runtime_err!(IndexOutOfBounds, Some(span.clone()), "Index out of bounds."),
+        },
+
+        _ => runtime_err!(TypeMismatch, Some(span.clone()), "Index must be an integer."),
+    }
+}
+
 #[derive(Debug)]
-pub struct Evaluator<'parent_scope> {
-    parent_scope: Option<&'parent_scope Evaluator<'parent_scope>>,
-    builtin_functions: &'parent_scope HashMap<String, Value>,
-    variables: HashMap<String, Value>,
+pub struct Evaluator<'builtins> {
+    builtin_functions: &'builtins HashMap<String, Value>,
+    environment: Rc<RefCell<Environment>>,
 }
 
-impl<'parent_scope, 'a> Evaluator<'parent_scope> {
-    pub fn new(
-        parent_scope: Option<&'parent_scope Evaluator>,
-        builtin_functions: &'parent_scope HashMap<String, Value>,
+impl<'builtins, 'a> Evaluator<'builtins> {
+    pub fn new(builtin_functions: &'builtins HashMap<String, Value>) -> Self {
+        Evaluator::with_environment(Rc::new(RefCell::new(Environment::default())), builtin_functions)
+    }
+
+    fn with_environment(
+        environment: Rc<RefCell<Environment>>,
+        builtin_functions: &'builtins HashMap<String, Value>,
     ) -> Self {
         Evaluator {
-            variables: HashMap::new(),
-            parent_scope,
             builtin_functions,
+            environment,
         }
     }
 
-    fn get_value(&self, name: &String) -> Result<&Value, String> {
+    // Variables defined in the outermost scope, sorted by name.
+    pub fn variables(&self) -> Vec<(String, Value)> {
+        let mut variables: Vec<(String, Value)> = self.environment.borrow().variables()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        variables.sort_by(|a, b| a.0.cmp(&b.0));
+
+        variables
+    }
+
+    fn get_value(&self, name: &String, span: &Span) -> Result<Value, NeesyError> {
         if let Some(builtin) = self.builtin_functions.get(name) {
-            return Ok(builtin);
+            return Ok(builtin.clone());
         } 
      
-        Ok(match self.variables.get(name) {
-            Some(value) => value,
-            None => {
-                if let Some(ps) = self.parent_scope {
-                    ps.get_value(name)?
-                } else {
-                    return cannot_find_var_err!(name);
+        match self.environment.borrow().get(name) {
+            Some(value) => Ok(value),
+            None => cannot_find_var_err!(span, name),
+        }
+    }
+
+    pub fn evaluate(&mut self, expression: &'a Spanned<Expression>) -> Result<Value, NeesyError> {
+        match self.eval(expression) {
+            Ok(value) => Ok(value),
+            Err(Unwind::Error(err)) => Err(err),
+            Err(Unwind::Return(_, span)) => return_outside_function_err!(span),
+            Err(Unwind::Break(span)) | Err(Unwind::Continue(span)) => loop_control_outside_loop_err!(span),
+        }
+    }
+
+    // Calls a function, builtin or struct constructor. `name` is only used
+    // in error messages.
+    fn call(&mut self, callee: Value, name: &str, args: Vec<Value>, span: &Span) -> Result<Value, Unwind> {
+        match callee {
+            Value::BuiltinFunction(param_count, func) => {
+                if args.len() < param_count {
+                    return not_enough_params_err!(span, name, param_count, args);
+                }
+
+                func(args).map_err(|err| Unwind::Error(err.with_span(span)))
+            },
+
+            Value::Function(t_params, t_stmts, closure) => {
+                let param_count = t_params.len();
+                
+                if args.len() != param_count {
+                    return not_enough_params_err!(span, name, param_count, args);
+                }
+
+                let mut environment = Environment::for_call(closure);
+                for (t_param, arg) in t_params.iter().zip(args) {
+                    environment.define(t_param.clone(), arg);
+                }
+
+                let mut subeval = Evaluator::with_environment(
+                    Rc::new(RefCell::new(environment)), 
+                    self.builtin_functions
+                );
+
+                match subeval.eval_block(&t_stmts) {
+                    Err(Unwind::Return(value, _)) => Ok(value),
+                    Err(Unwind::Break(span)) | Err(Unwind::Continue(span)) => {
+                        loop_control_outside_loop_err!(span)
+                    }
+                    result => result,
+                }
+            },
+
+            Value::StructType(struct_type) => {
+                let param_count = struct_type.fields.len();
+
+                if args.len() != param_count {
+                    return not_enough_params_err!(span, name, param_count, args);
+                }
+
+                Ok(Value::Struct(struct_type, Rc::new(RefCell::new(args))))
+            },
+
+            _ => not_a_function_err!(span, name),
+        }
+    }
+
+    fn get_index(&self, container: Value, index: Value, span: &Span) -> Result<Value, Unwind> {
+        match container {
+            Value::Array(arr) => {
+                let array = arr.borrow();
+
+                // Indexing with a range gives a new array with the elements
+                // at each of its numbers.
+                if let Value::Range(range) = index {
+                    let slice = range.iter()
+                        .map(|i| array_index(&Value::Int(i), array.len(), span).map(|i| array[i].clone()))
+                        .collect::<Result<Vec<Value>, NeesyError>>()?;
+
+                    return Ok(Value::Array(Rc::new(RefCell::new(slice))));
+                }
+
+                let index = array_index(&index, array.len(), span)?;
+
+                Ok(array[index].clone())
+            }
+
+            Value::Map(map) => {
+                let key = MapKey::new(&index).map_err(|err| err.with_span(span))?;
+
+                match map.borrow().get(&key) {
+                    Some(value) => Ok(value.clone()),
+                    None => runtime_err!(KeyNotFound, Some(span.clone()), format!("Key {} not found.", key)),
                 }
             }
-        })
+
+            _ => runtime_err!(TypeMismatch, Some(span.clone()), format!("Cannot index {:?}.", container)),
+        }
     }
 
-    pub fn evaluate_block(&mut self, stmts: &'a Vec<Expression>) -> Result<Value, String> {
-        if stmts.len() == 0 {
-            return Ok(Value::Void);
+    fn set_index(&self, container: Value, index: Value, value: Value, span: &Span) -> Result<Value, Unwind> {
+        match container {
+            Value::Array(arr) => {
+                let mut array = arr.borrow_mut();
+                let index = array_index(&index, array.len(), span)?;
+
+                array[index] = value.clone();
+
+                Ok(value)
+            }
+
+            Value::Map(map) => {
+                let key = MapKey::new(&index).map_err(|err| err.with_span(span))?;
+                map::set(&mut map.borrow_mut(), key, value.clone());
+
+                Ok(value)
+            }
+
+            _ => runtime_err!(TypeMismatch, Some(span.clone()), format!("Cannot index {:?}.", container)),
+        }
+    }
+
+    // Whether `value` matches `pattern`, defining the variables it binds in
+    // `environment` along the way.
+    fn match_pattern(&mut self, pattern: &'a Pattern, value: &Value, environment: &mut Environment) -> Result<bool, Unwind> {
+        match pattern {
+            Pattern::Wildcard => Ok(true),
+
+            Pattern::Binding(name) => {
+                environment.define(name.clone(), value.clone());
+                Ok(true)
+            }
+
+            // Values that can't be compared, like a string and a number, just
+            // don't match.
+            Pattern::Literal(literal) => {
+                let literal = self.eval(literal)?;
+                Ok(matches!(equals(literal, value.clone()), Ok(Value::Bool(true))))
+            }
+
+            Pattern::Array(items, rest) => {
+                let array = match value {
+                    Value::Array(arr) => arr.borrow().clone(),
+                    _ => return Ok(false),
+                };
+
+                let length_matches = match rest {
+                    Some(_) => array.len() >= items.len(),
+                    None => array.len() == items.len(),
+                };
+
+                if !length_matches {
+                    return Ok(false);
+                }
+
+                for (item, value) in items.iter().zip(array.iter()) {
+                    if !self.match_pattern(item, value, environment)? {
+                        return Ok(false);
+                    }
+                }
+
+                match rest {
+                    Some(rest) => {
+                        let others = Value::Array(Rc::new(RefCell::new(array[items.len()..].to_vec())));
+                        self.match_pattern(rest, &others, environment)
+                    }
+
+                    None => Ok(true),
+                }
+            }
         }
+    }
 
-        for i in 0..stmts.len() - 1 {
-            self.evaluate(&stmts[i])?;
+    fn eval_block(&mut self, stmts: &'a [Spanned<Expression>]) -> Result<Value, Unwind> {
+        let mut result = Value::Void;
+
+        for stmt in stmts {
+            result = self.eval(stmt)?;
         }
 
-        return Ok(self.evaluate(&stmts[stmts.len() - 1])?);
+        Ok(result)
     }
 
-    pub fn evaluate(&mut self, expression: &'a Expression) -> Result<Value, String> {
-        match expression {
+    fn eval(&mut self, expression: &'a Spanned<Expression>) -> Result<Value, Unwind> {
+        let span = &expression.span;
+
+        match &expression.node {
             Expression::Infix(op, l, r) => match op {
-                InfixOperator::Add => self.evaluate(*(&l))? + self.evaluate(*(&r))?,
-                InfixOperator::Sub => self.evaluate(*(&l))? - self.evaluate(*(&r))?,
-                InfixOperator::Mul => self.evaluate(*(&l))? * self.evaluate(*(&r))?,
-                InfixOperator::Div => self.evaluate(*(&l))? / self.evaluate(*(&r))?,
+                InfixOperator::Add => self.eval(l)? + self.eval(r)?,
+                InfixOperator::Sub => self.eval(l)? - self.eval(r)?,
+                InfixOperator::Mul => self.eval(l)? * self.eval(r)?,
+                InfixOperator::Div => self.eval(l)? / self.eval(r)?,
+                InfixOperator::Mod => self.eval(l)? % self.eval(r)?,
+                InfixOperator::FloorDiv => basic::floor_div(self.eval(l)?, self.eval(r)?),
+                InfixOperator::Pow => basic::pow(self.eval(l)?, self.eval(r)?),
+
+                // The right operand is only evaluated when the left one
+                // doesn't already decide the result.
+                InfixOperator::And | InfixOperator::Or => {
+                    let (decisive, name) = match op {
+                        InfixOperator::Or => (true, "apply or to"),
+                        _ => (false, "apply and to"),
+                    };
+
+                    match self.eval(l)? {
+                        Value::Bool(b) if b == decisive => Ok(Value::Bool(b)),
+
+                        Value::Bool(_) => match self.eval(r)? {
+                            Value::Bool(b) => Ok(Value::Bool(b)),
+                            x => invalid_operand_err!(name, x),
+                        },
+
+                        x => invalid_operand_err!(name, x),
+                    }
+                },
                 
-                InfixOperator::Equals => equals(self.evaluate(*(&l))?, self.evaluate(*(&r))?),
+                InfixOperator::Equals => equals(self.eval(l)?, self.eval(r)?),
+                InfixOperator::NotEquals => not_equals(self.eval(l)?, self.eval(r)?),
                 InfixOperator::GreaterThanOrEquals => { 
-                    greater_than_equals(self.evaluate(*(&l))?, self.evaluate(*(&r))?)
+                    greater_than_equals(self.eval(l)?, self.eval(r)?)
                 },
                 InfixOperator::LessThanOrEquals => {
-                    less_than_equals(self.evaluate(*(&l))?, self.evaluate(*(&r))?)
+                    less_than_equals(self.eval(l)?, self.eval(r)?)
                 },
                 InfixOperator::LessThan => {
-                    less_than(self.evaluate(*(&l))?, self.evaluate(*(&r))?)
+                    less_than(self.eval(l)?, self.eval(r)?)
                 },
                 InfixOperator::GreaterThan => {
-                    greater_than(self.evaluate(*(&l))?, self.evaluate(*(&r))?)
+                    greater_than(self.eval(l)?, self.eval(r)?)
                 },
-            },
+            }.map_err(|err| Unwind::Error(err.with_span(span))),
+
+            Expression::Prefix(op, l) => {
+                let value = self.eval(l)?;
 
-            Expression::Prefix(op, l) => match op {
-                PrefixOperator::Positive => self.evaluate(*(&l)),
-                _ => unreachable!(),
+                match op {
+                    PrefixOperator::Positive => basic::positive(value),
+                    PrefixOperator::Negative => -value,
+                    PrefixOperator::Not => !value,
+                }.map_err(|err| Unwind::Error(err.with_span(span)))
             },
 
-            Expression::Id(name) => Ok(self.get_value(&name)?.clone()),
+            Expression::Id(name) => Ok(self.get_value(name, span)?),
 
-            Expression::Assignment(name, expr) => {
-                let value = self.evaluate(*(&expr))?;
+            Expression::Assignment(name, expr, _) => {
+                let value = self.eval(expr)?;
 
                 match value {
-                    Value::Void => cannot_assign_void_to_var_err!(name),
+                    Value::Void => cannot_assign_void_to_var_err!(span, name),
 
                     _ => {
-                        if !self.builtin_functions.get(name).is_none() {
-                            return cannot_assign_to_builtin_err!(name)
+                        if self.builtin_functions.get(name).is_some() {
+                            return cannot_assign_to_builtin_err!(span, name)
                         }
                         
-                        self.variables.insert(name.clone(), value.clone());
+                        self.environment.borrow_mut().assign(name.clone(), value.clone());
                         Ok(value)
                     }
                 }
             }
 
-            Expression::Num(n) => Ok(Value::Number(n.clone())),
+            Expression::OuterAssignment(name, expr) => {
+                let value = self.eval(expr)?;
+
+                if let Value::Void = value {
+                    return cannot_assign_void_to_var_err!(span, name);
+                }
+
+                if self.environment.borrow_mut().assign_outer(name.clone(), value.clone()) {
+                    Ok(value)
+                } else {
+                    cannot_find_var_err!(span, name)
+                }
+            }
+
+            Expression::FieldAssignment(object, field, expr) => {
+                let object = self.eval(object)?;
+                let value = self.eval(expr)?;
+
+                match object {
+                    Value::Struct(struct_type, values) => {
+                        if let Value::Void = value {
+                            return cannot_assign_void_to_var_err!(span, field);
+                        }
+
+                        match struct_type.field_index(field) {
+                            Some(index) => {
+                                values.borrow_mut()[index] = value.clone();
+                                Ok(value)
+                            }
+
+                            None => unknown_field_err!(span, struct_type, field),
+                        }
+                    }
+
+                    _ => not_a_struct_err!(span, object, field),
+                }
+            }
+
+            Expression::StructDeclaration(name, fields) => {
+                if self.builtin_functions.get(name).is_some() {
+                    return cannot_assign_to_builtin_err!(span, name)
+                }
+
+                let struct_type = StructType::new(name.clone(), fields.clone());
+                self.environment.borrow_mut().define(name.clone(), Value::StructType(Rc::new(struct_type)));
+
+                Ok(Value::Void)
+            }
+
+            Expression::FieldAccess(object, field) => {
+                match self.eval(object)? {
+                    Value::Struct(struct_type, values) => match struct_type.field_index(field) {
+                        Some(index) => Ok(values.borrow()[index].clone()),
+                        None => unknown_field_err!(span, struct_type, field),
+                    },
+
+                    object => not_a_struct_err!(span, object, field),
+                }
+            }
+
+            Expression::Int(n) => Ok(Value::Int(*n)),
+            Expression::BigInt(n) => Ok(Value::BigInt(n.clone())),
+            Expression::Decimal(n) => Ok(Value::Decimal(*n)),
+            Expression::Num(n) => Ok(Value::Number(*n)),
             Expression::Str(string) => Ok(Value::Str(string.clone())),
+
+            Expression::InterpolatedStr(parts) => {
+                let mut string = String::new();
+
+                for part in parts {
+                    let value = self.eval(part)?;
+                    let text = builtin::convert::stringify(&value).map_err(|err| err.with_span(&part.span))?;
+
+                    string.push_str(&text);
+                }
+
+                Ok(Value::Str(string))
+            },
             Expression::Function(params, smts) => Ok(Value::Function(
                 Rc::new(params.clone()),
                 Rc::new(smts.clone()),
+                self.environment.clone(),
             )),
 
             Expression::If(expr, stmts, else_stmts) => {
-                if let Value::Bool(result) = self.evaluate(*(&expr))? {
+                if let Value::Bool(result) = self.eval(expr)? {
                     if result {
-                        Ok(self.evaluate_block(stmts)?)
+                        Ok(self.eval_block(stmts)?)
                     } else {
                         let else_stmts_ref = else_stmts.as_ref();
 
                         if else_stmts.is_none() {
                             Ok(Value::Void)
                         } else {
-                            Ok(self.evaluate_block(else_stmts_ref.unwrap())?)
+                            Ok(self.eval_block(else_stmts_ref.unwrap())?)
                         }
                     }
                 } else {
-                    unreachable!()
+                    condition_not_bool_err!(expr.span)
                 }
             },
 
 
             Expression::While(expr, stmts) => {
-                if let Value::Bool(result) = self.evaluate(*(&expr))? {
-                    if !result {
-                        return Ok(Value::Void)
-                    } 
-                    
-                    loop {
-                        self.evaluate_block(stmts)?;
-
-                        match self.evaluate(*(&expr))? {
-                            Value::Bool(v) => if !v { break; },
-
-                            _ => unreachable!()
+                loop {
+                    match self.eval(expr)? {
+                        Value::Bool(true) => {},
+                        Value::Bool(false) => break,
+
+                        _ => return condition_not_bool_err!(expr.span)
+                    }
+
+                    match self.eval_block(stmts) {
+                        Err(Unwind::Break(_)) => break,
+                        Err(Unwind::Continue(_)) => continue,
+                        Err(unwind) => return Err(unwind),
+                        Ok(_) => {}
+                    }
+                }
+
+                Ok(Value::Void)
+            }
+
+            Expression::Match(subject, arms) => {
+                let value = self.eval(subject)?;
+
+                for arm in arms {
+                    // Variables bound by the pattern are only visible in the
+                    // arm's guard and body.
+                    let mut environment = Environment::new(Some(self.environment.clone()));
+
+                    if !self.match_pattern(&arm.pattern, &value, &mut environment)? {
+                        continue;
+                    }
+
+                    let mut subeval = Evaluator::with_environment(
+                        Rc::new(RefCell::new(environment)),
+                        self.builtin_functions
+                    );
+
+                    if let Some(guard) = &arm.guard {
+                        match subeval.eval(guard)? {
+                            Value::Bool(true) => {},
+                            Value::Bool(false) => continue,
+                            _ => return condition_not_bool_err!(guard.span),
                         }
                     }
 
-                    Ok(Value::Void)
-                } else {
-                    unreachable!()
+                    return subeval.eval(&arm.body);
+                }
+
+                runtime_err!(NoMatchingArm, Some(subject.span.clone()), format!("No arm matches {}.", value))
+            }
+
+            Expression::For(name, iterable, stmts) => {
+                if self.builtin_functions.get(name).is_some() {
+                    return cannot_assign_to_builtin_err!(span, name)
+                }
+
+                let iterable_span = &iterable.span;
+                let items = iterate(self.eval(iterable)?).map_err(|err| err.with_span(iterable_span))?;
+
+                for item in items {
+                    // Each iteration gets its own scope, so closures created
+                    // in the body capture the current value of the variable.
+                    let mut environment = Environment::new(Some(self.environment.clone()));
+                    environment.define(name.clone(), item);
+
+                    let mut subeval = Evaluator::with_environment(
+                        Rc::new(RefCell::new(environment)),
+                        self.builtin_functions
+                    );
+
+                    match subeval.eval_block(stmts) {
+                        Err(Unwind::Break(_)) => break,
+                        Err(Unwind::Continue(_)) => continue,
+                        Err(unwind) => return Err(unwind),
+                        Ok(_) => {}
+                    }
                 }
+
+                Ok(Value::Void)
+            }
+
+            Expression::Range(start, end, step, inclusive) => {
+                let start = range_bound(self.eval(start)?, &start.span)?;
+                let end = range_bound(self.eval(end)?, &end.span)?;
+
+                let step = match step {
+                    Some(step) => match range_bound(self.eval(step)?, &step.span)? {
+                        0 => return runtime_err!(InvalidRange, Some(step.span.clone()), "Range step cannot be zero."),
+                        n => n,
+                    },
+
+                    None => 1,
+                };
+
+                Ok(Value::Range(Range::new(start, end, step, *inclusive)))
             }
 
             Expression::Void => Ok(Value::Void),
-            Expression::Bool(val) => Ok(Value::Bool(val.clone())),
+            Expression::Bool(val) => Ok(Value::Bool(*val)),
 
             Expression::Array(exprs) => Ok(Value::Array(Rc::new({
                 let mut values : Vec<Value> = Vec::new();
                 for expr in exprs {
-                    values.push(self.evaluate(expr)?);
+                    values.push(self.eval(expr)?);
                 }
                 
                 RefCell::new(values)
             }))),
 
-            Expression::FunctionCall(name, params) => {
-                match self.get_value(&name)?.clone() {
-                    Value::Array(arr) => {
+            Expression::Map(entries) => {
+                let mut map : LinkedHashMap<MapKey, Value> = LinkedHashMap::new();
+
+                for (key, value) in entries {
+                    let key_span = &key.span;
+                    let key = MapKey::new(&self.eval(key)?).map_err(|err| err.with_span(key_span))?;
+
+                    map::set(&mut map, key, self.eval(value)?);
+                }
+
+                Ok(Value::Map(Rc::new(RefCell::new(map))))
+            },
+
+            Expression::Call(callee, params) => {
+                let name = callee_name(&callee.node);
 
+                match self.eval(callee)? {
+                    container @ Value::Array(_) | container @ Value::Map(_) => {
                         match params.len() {
-                            0 => {
-                                return Err(format!("Index not specified"))
-                            }
+                            0 => runtime_err!(IndexOutOfBounds, Some(span.clone()), "Index not specified"),
 
                             1 => {
-                                if let Value::Number(n) = self.evaluate(&params[0])? {
-                                    let array_mutable = arr.borrow();
-                                    
-                                    let index = n as usize;
-                                    if index >= array_mutable.len() {
-                                        return Err(format!("Index out of bounds."))
-                                    } 
-        
-                                    return Ok(array_mutable[index].clone());
-                                } else {
-                                    return Err(format!("Index must be a number."))
-                                }
-                            },
-
-                            2 | _ => {
-                                if let Value::Number(n) = self.evaluate(&params[0])? {
-                                    let result =  self.evaluate(&params[1])?;
-                                    
-                                    let mut array_mutable = arr.borrow_mut();
-
-                                    let index = n as usize;
-                                    if index >= array_mutable.len() {
-                                        return Err(format!("Index out of bounds."))
-                                    }
-
-                                    array_mutable[index] = result;
-
-                                    return Ok(array_mutable[index].clone());
-                                } else {
-                                    return Err(format!("Index must be a number."))
-                                }
+                                let index = self.eval(&params[0])?;
+                                self.get_index(container, index, span)
                             }
-                        }
-                    },
 
-                    Value::BuiltinFunction(param_count, func) => {
-                        let mut resolved_params: Vec<Value> = vec![]; 
-                        
-                        if params.len() < param_count {
-                            return not_enough_params_err!(name, param_count, params);
-                        }
+                            _ => {
+                                let index = self.eval(&params[0])?;
+                                let value = self.eval(&params[1])?;
 
-                        for param in params {
-                            resolved_params.push(self.evaluate(param)?);
+                                self.set_index(container, index, value, span)
+                            }
                         }
-                        
-                        return func(resolved_params);
                     },
 
-                    Value::Function(t_params, t_stmts) => {
-                        let param_count = t_params.len();
-                        
-                        if params.len() != param_count {
-                            return not_enough_params_err!(name, param_count, params);
-                        }
-        
-                        let mut subeval = Evaluator::new(self.parent_scope, self.builtin_functions);
-                        for (i, t_param) in t_params.iter().enumerate() {
-                            subeval
-                                .variables
-                                .insert(t_param.clone(), self.evaluate(&params[i])?);
-                        }
-        
-                        if self.parent_scope.is_none() {
-                            subeval.parent_scope = Some(self);
-                        } else {
-                            subeval.parent_scope = Some(self.parent_scope.unwrap());
+                    callee => {
+                        let mut args : Vec<Value> = vec![];
+                        for param in params {
+                            args.push(self.eval(param)?);
                         }
-        
-                        return subeval.evaluate_block(&t_stmts)
-                    },
 
-                    _ => return not_a_function_err!(name),
+                        self.call(callee, &name, args, span)
+                    }
+                }
+            }
+
+            Expression::MethodCall(object, method, params) => {
+                let object = self.eval(object)?;
+
+                let mut args : Vec<Value> = vec![];
+                for param in params {
+                    args.push(self.eval(param)?);
+                }
+
+                // A struct field holding a function is called as is...
+                if let Value::Struct(struct_type, values) = &object {
+                    if let Some(index) = struct_type.field_index(method) {
+                        let callee = values.borrow()[index].clone();
+                        return self.call(callee, method, args, span);
+                    }
+                }
+
+                // ...otherwise `x.f(a)` calls `f` with `x` as its first argument.
+                let callee = self.get_value(method, span)?;
+                args.insert(0, object);
+
+                self.call(callee, method, args, span)
+            }
+
+            Expression::Index(container, index) => {
+                let container = self.eval(container)?;
+                let index = self.eval(index)?;
+
+                self.get_index(container, index, span)
+            }
+
+            Expression::IndexAssignment(container, index, expr) => {
+                let container = self.eval(container)?;
+                let index = self.eval(index)?;
+                let value = self.eval(expr)?;
+
+                self.set_index(container, index, value, span)
+            }
+
+            Expression::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.eval(expr)?,
+                    None => Value::Void,
                 };
 
-                
+                Err(Unwind::Return(value, span.clone()))
             }
 
-            _ => unreachable!(),
+            Expression::Break => Err(Unwind::Break(span.clone())),
+            Expression::Continue => Err(Unwind::Continue(span.clone())),
         }
     }
 }
\ No newline at end of file
@@ -1,102 +1,346 @@
+use crate::error::NeesyError;
 use crate::evaluator::Value;
 
-use std::ops::{Add, Sub, Div, Mul};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{FromPrimitive, ToPrimitive, Zero};
+use rust_decimal::Decimal;
+
+use std::convert::TryFrom;
+use std::ops::{Add, Sub, Div, Mul, Rem, Neg};
+
+// The operands of an arithmetic operator, converted to a common type. Integers
+// are promoted to big integers, then to decimals or floats. Decimals and floats
+// don't mix, since that would silently lose the exactness of the decimal.
+pub(super) enum Operands {
+    Int(i64, i64),
+    BigInt(BigInt, BigInt),
+    Decimal(Decimal, Decimal),
+    Float(f64, f64),
+}
+
+fn to_big_int(x: &Value) -> Option<BigInt> {
+    match x {
+        Value::Int(x) => Some(BigInt::from(*x)),
+        Value::BigInt(x) => Some(x.clone()),
+
+        _ => None
+    }
+}
+
+fn to_decimal(x: &Value) -> Option<Decimal> {
+    match x {
+        Value::Int(x) => Some(Decimal::from(*x)),
+        Value::BigInt(x) => x.to_i128().and_then(Decimal::from_i128),
+        Value::Decimal(x) => Some(*x),
+
+        _ => None
+    }
+}
+
+fn to_float(x: &Value) -> Option<f64> {
+    match x {
+        Value::Int(x) => Some(*x as f64),
+        Value::BigInt(x) => x.to_f64(),
+        Value::Number(x) => Some(*x),
+
+        _ => None
+    }
+}
+
+pub(super) fn numeric_operands(x: &Value, y: &Value) -> Option<Operands> {
+    match (x, y) {
+        (Value::Int(x), Value::Int(y)) => Some(Operands::Int(*x, *y)),
+
+        (Value::Decimal(_), Value::Number(_)) | (Value::Number(_), Value::Decimal(_)) => None,
+
+        (Value::Decimal(_), _) | (_, Value::Decimal(_)) => {
+            Some(Operands::Decimal(to_decimal(x)?, to_decimal(y)?))
+        }
+
+        (Value::Number(_), _) | (_, Value::Number(_)) => {
+            Some(Operands::Float(to_float(x)?, to_float(y)?))
+        }
+
+        _ => Some(Operands::BigInt(to_big_int(x)?, to_big_int(y)?))
+    }
+}
+
+fn checked_int(result: Option<i64>, op_name: &str) -> Result<Value, NeesyError> {
+    match result {
+        Some(n) => Ok(Value::Int(n)),
+        None => runtime_err!(NumericOverflow, None, format!("Integer overflow when trying to {}", op_name))
+    }
+}
+
+fn checked_decimal(result: Option<Decimal>, op_name: &str) -> Result<Value, NeesyError> {
+    match result {
+        Some(n) => Ok(Value::Decimal(n)),
+        None => runtime_err!(NumericOverflow, None, format!("Decimal overflow when trying to {}", op_name))
+    }
+}
+
 impl Add for Value {
-    type Output = Result<Value, String>;
+    type Output = Result<Value, NeesyError>;
 
-    fn add(self, other: Value) -> Result<Value, String> {
-        match self {
-            Value::Number(x) => {
-                if let Value::Number(y) = other {
-                    Ok(Value::Number(x + y))
-                } else {
-                    return invalid_operands_err!("sum", x, other);
-                }
-            },
+    fn add(self, other: Value) -> Result<Value, NeesyError> {
+        match numeric_operands(&self, &other) {
+            Some(Operands::Int(x, y)) => checked_int(x.checked_add(y), "sum"),
+            Some(Operands::BigInt(x, y)) => Ok(Value::BigInt(x + y)),
+            Some(Operands::Decimal(x, y)) => checked_decimal(x.checked_add(y), "sum"),
+            Some(Operands::Float(x, y)) => Ok(Value::Number(x + y)),
 
-            Value::Str(x) => {
-                if let Value::Str(y) = other {
-                    let mut new = String::new();
+            None => match self {
+                Value::Str(x) => {
+                    if let Value::Str(y) = other {
+                        let mut new = String::new();
 
-                    new.push_str(&x);
-                    new.push_str(&y);
-                    
-                    Ok(Value::Str(new))
-                } else {
-                    return invalid_operands_err!("sum", x, other);
+                        new.push_str(&x);
+                        new.push_str(&y);
+                        
+                        Ok(Value::Str(new))
+                    } else {
+                        invalid_operands_err!("sum", x, other)
+                    }
                 }
-            }
 
-            _ => unreachable!()
+                x => invalid_operands_err!("sum", x, other)
+            }
         }
     }
 }
 
 
 impl Sub for Value {
-    type Output = Result<Value, String>;
+    type Output = Result<Value, NeesyError>;
 
-    fn sub(self, other: Value) -> Result<Value, String> {
-        match self {
-            Value::Number(x) => {
-                if let Value::Number(y) = other {
-                    Ok(Value::Number(x - y))
-                } else {
-                    return invalid_operands_err!("subtract", x, other);
+    fn sub(self, other: Value) -> Result<Value, NeesyError> {
+        match numeric_operands(&self, &other) {
+            Some(Operands::Int(x, y)) => checked_int(x.checked_sub(y), "subtract"),
+            Some(Operands::BigInt(x, y)) => Ok(Value::BigInt(x - y)),
+            Some(Operands::Decimal(x, y)) => checked_decimal(x.checked_sub(y), "subtract"),
+            Some(Operands::Float(x, y)) => Ok(Value::Number(x - y)),
+
+            None => invalid_operands_err!("subtract", self, other)
+        }
+    }
+}
+
+
+impl Mul for Value {
+    type Output = Result<Value, NeesyError>;
+
+    fn mul(self, other: Value) -> Result<Value, NeesyError> {
+        match numeric_operands(&self, &other) {
+            Some(Operands::Int(x, y)) => checked_int(x.checked_mul(y), "multiply"),
+            Some(Operands::BigInt(x, y)) => Ok(Value::BigInt(x * y)),
+            Some(Operands::Decimal(x, y)) => checked_decimal(x.checked_mul(y), "multiply"),
+            Some(Operands::Float(x, y)) => Ok(Value::Number(x * y)),
+
+            None => match self {
+                Value::Str(x) => {
+                    if let Value::Int(y) = other {
+                        let count = usize::try_from(y.max(0)).unwrap_or(usize::MAX);
+
+                        // Lengths past isize::MAX can't be allocated at all.
+                        match count.checked_mul(x.len()) {
+                            Some(length) if isize::try_from(length).is_ok() => Ok(Value::Str(x.repeat(count))),
+                            _ => runtime_err!(NumericOverflow, None, "String too long when trying to multiply"),
+                        }
+                    } else {
+                        invalid_operands_err!("multiply", x, other)
+                    }
                 }
+
+                x => invalid_operands_err!("multiply", x, other)
             }
+        }
+    }
+}
+
 
-            _ => unreachable!()
+// Integers are divided as floats: use `div` to get an integer. Decimals stay
+// decimals, rounded to their 28 digits of precision.
+impl Div for Value {
+    type Output = Result<Value, NeesyError>;
+
+    fn div(self, other: Value) -> Result<Value, NeesyError> {
+        match numeric_operands(&self, &other) {
+            Some(Operands::Int(x, y)) => Ok(Value::Number(x as f64 / y as f64)),
+            Some(Operands::BigInt(x, y)) => Ok(Value::Number(big_int_ratio(&x, &y))),
+
+            Some(Operands::Decimal(_, y)) if y.is_zero() => runtime_err!(DivisionByZero, None, "Cannot divide by zero"),
+            Some(Operands::Decimal(x, y)) => checked_decimal(x.checked_div(y), "divide"),
+
+            Some(Operands::Float(x, y)) => Ok(Value::Number(x / y)),
+
+            None => invalid_operands_err!("divide", self, other)
         }
     }
 }
 
+// `x / y` as a float, even when `x` and `y` themselves don't fit in one: both
+// are scaled down to about 1000 bits first, which f64 can represent.
+fn big_int_ratio(x: &BigInt, y: &BigInt) -> f64 {
+    let shift = x.bits().max(y.bits()).saturating_sub(1000);
 
-impl Mul for Value {
-    type Output = Result<Value, String>;
+    let x = (x >> shift).to_f64().unwrap_or(f64::NAN);
+    let y = (y >> shift).to_f64().unwrap_or(f64::NAN);
 
-    fn mul(self, other: Value) -> Result<Value, String> {
-        match self {
-            Value::Number(x) => {
-                if let Value::Number(y) = other {
-                    Ok(Value::Number(x * y))
+    x / y
+}
+
+
+// The result takes the sign of the divisor, matching `div`:
+// `a == (a div b) * b + a % b` always holds.
+impl Rem for Value {
+    type Output = Result<Value, NeesyError>;
+
+    fn rem(self, other: Value) -> Result<Value, NeesyError> {
+        match numeric_operands(&self, &other) {
+            Some(Operands::Int(_, 0)) => runtime_err!(DivisionByZero, None, "Cannot take the remainder of a division by zero"),
+            Some(Operands::Int(x, y)) => {
+                let remainder = x.wrapping_rem(y);
+
+                if remainder != 0 && (remainder < 0) != (y < 0) {
+                    Ok(Value::Int(remainder + y))
                 } else {
-                    return invalid_operands_err!("multiply", x, other);
+                    Ok(Value::Int(remainder))
                 }
-            },
+            }
 
-            Value::Str(x) => {
-                if let Value::Number(y ) = other {
-                    let mut new = String::new();
+            Some(Operands::BigInt(_, y)) if y.is_zero() => runtime_err!(DivisionByZero, None, "Cannot take the remainder of a division by zero"),
+            Some(Operands::BigInt(x, y)) => Ok(Value::BigInt(x.mod_floor(&y))),
 
-                    for _ in 0..(y as usize)  { new.push_str(&x); }
+            Some(Operands::Decimal(_, y)) if y.is_zero() => runtime_err!(DivisionByZero, None, "Cannot take the remainder of a division by zero"),
+            Some(Operands::Decimal(x, y)) => {
+                let remainder = x % y;
 
-                    Ok(Value::Str(new))
+                if !remainder.is_zero() && remainder.is_sign_negative() != y.is_sign_negative() {
+                    checked_decimal(remainder.checked_add(y), "take the remainder of")
                 } else {
-                    return invalid_operands_err!("multiply", x, other);
+                    Ok(Value::Decimal(remainder))
                 }
             }
 
-            _ => unreachable!()
+            Some(Operands::Float(x, y)) => Ok(Value::Number(x - y * (x / y).floor())),
+
+            None => invalid_operands_err!("take the remainder of", self, other)
         }
     }
 }
 
 
-impl Div for Value {
-    type Output = Result<Value, String>;
+impl Neg for Value {
+    type Output = Result<Value, NeesyError>;
 
-    fn div(self, other: Value) -> Result<Value, String> {
+    fn neg(self) -> Result<Value, NeesyError> {
         match self {
-            Value::Number(x) => {
-                if let Value::Number(y) = other {
-                    Ok(Value::Number(x / y))
-                } else {
-                    return invalid_operands_err!("divide", x, other);
-                }
+            Value::Int(x) => checked_int(x.checked_neg(), "negate"),
+            Value::BigInt(x) => Ok(Value::BigInt(-x)),
+            Value::Decimal(x) => Ok(Value::Decimal(-x)),
+            Value::Number(x) => Ok(Value::Number(-x)),
+
+            x => invalid_operand_err!("negate", x)
+        }
+    }
+}
+
+pub fn positive(x: Value) -> Result<Value, NeesyError> {
+    match x {
+        Value::Int(_) | Value::BigInt(_) | Value::Decimal(_) | Value::Number(_) => Ok(x),
+
+        x => invalid_operand_err!("apply unary + to", x)
+    }
+}
+
+pub fn floor_div(x: Value, other: Value) -> Result<Value, NeesyError> {
+    match numeric_operands(&x, &other) {
+        Some(Operands::Int(_, 0)) => runtime_err!(DivisionByZero, None, "Cannot divide by zero"),
+        Some(Operands::Int(x, y)) => {
+            let quotient = match x.checked_div(y) {
+                Some(quotient) => quotient,
+                None => return checked_int(None, "divide"),
+            };
+
+            if x % y != 0 && (x < 0) != (y < 0) {
+                Ok(Value::Int(quotient - 1))
+            } else {
+                Ok(Value::Int(quotient))
             }
+        }
+
+        Some(Operands::BigInt(_, y)) if y.is_zero() => runtime_err!(DivisionByZero, None, "Cannot divide by zero"),
+        Some(Operands::BigInt(x, y)) => Ok(Value::BigInt(x.div_floor(&y))),
+
+        Some(Operands::Decimal(_, y)) if y.is_zero() => runtime_err!(DivisionByZero, None, "Cannot divide by zero"),
+        Some(Operands::Decimal(x, y)) => checked_decimal(x.checked_div(y).map(|q| q.floor()), "divide"),
+
+        Some(Operands::Float(x, y)) => Ok(Value::Number((x / y).floor())),
+
+        None => invalid_operands_err!("divide", x, other)
+    }
+}
 
-            _ => unreachable!()
+// Integers and decimals raised to an integer power stay exact, except for
+// integers raised to a negative power, which give a float. So do decimals
+// raised to a fractional power.
+pub fn pow(x: Value, other: Value) -> Result<Value, NeesyError> {
+    match numeric_operands(&x, &other) {
+        Some(Operands::Int(x, y)) if y >= 0 => {
+            let result = u32::try_from(y).ok().and_then(|y| x.checked_pow(y));
+            checked_int(result, "raise")
         }
+
+        Some(Operands::Int(x, y)) => Ok(Value::Number((x as f64).powf(y as f64))),
+
+        Some(Operands::BigInt(x, y)) => match y.to_u32() {
+            Some(y) => Ok(Value::BigInt(x.pow(y))),
+
+            None if y < BigInt::zero() => match (x.to_f64(), y.to_f64()) {
+                (Some(x), Some(y)) => Ok(Value::Number(x.powf(y))),
+                _ => runtime_err!(NumericOverflow, None, "Integer overflow when trying to raise"),
+            },
+
+            None => runtime_err!(NumericOverflow, None, "Exponent too large"),
+        },
+
+        Some(Operands::Decimal(x, y)) if y.fract().is_zero() => match y.to_i64() {
+            Some(y) => checked_decimal(decimal_pow(x, y), "raise"),
+            None => runtime_err!(NumericOverflow, None, "Exponent too large"),
+        },
+
+        Some(Operands::Decimal(x, y)) => match (x.to_f64(), y.to_f64()) {
+            (Some(x), Some(y)) => Ok(Value::Number(x.powf(y))),
+            _ => runtime_err!(NumericOverflow, None, "Decimal overflow when trying to raise"),
+        },
+
+        Some(Operands::Float(x, y)) => Ok(Value::Number(x.powf(y))),
+
+        None => invalid_operands_err!("raise", x, other)
+    }
+}
+
+fn decimal_pow(base: Decimal, exponent: i64) -> Option<Decimal> {
+    let mut result = Decimal::ONE;
+    let mut square = base;
+    let mut remaining = exponent.unsigned_abs();
+
+    while remaining > 0 {
+        if remaining & 1 == 1 {
+            result = result.checked_mul(square)?;
+        }
+
+        remaining >>= 1;
+
+        if remaining > 0 {
+            square = square.checked_mul(square)?;
+        }
+    }
+
+    if exponent < 0 {
+        Decimal::ONE.checked_div(result)
+    } else {
+        Some(result)
     }
 }
@@ -1,18 +1,35 @@
 
+use crate::error::NeesyError;
 use crate::evaluator::Value;
+use crate::evaluator::basic::{numeric_operands, Operands};
+
+use std::ops::Not;
+use std::rc::Rc;
+
+impl Not for Value {
+    type Output = Result<Value, NeesyError>;
+
+    fn not(self) -> Result<Value, NeesyError> {
+        match self {
+            Value::Bool(b) => Ok(Value::Bool(!b)),
+
+            x => invalid_operand_err!("negate", x)
+        }
+    }
+}
 
 macro_rules! declare_boolean_op {
     ($name:ident, $op:tt) => { 
-        pub fn $name(x : Value, other: Value) -> Result<Value, String> {
-            match x {
-                Value::Number(n) => {
-                    if let Value::Number(y) = other {
-                        Ok(Value::Bool(n $op y))
-                    } else {
-                        return invalid_operands_err!("compare", n, other);
-                    }
-                },
+        pub fn $name(x : Value, other: Value) -> Result<Value, NeesyError> {
+            match numeric_operands(&x, &other) {
+                Some(Operands::Int(n, y)) => return Ok(Value::Bool(n $op y)),
+                Some(Operands::BigInt(n, y)) => return Ok(Value::Bool(n $op y)),
+                Some(Operands::Decimal(n, y)) => return Ok(Value::Bool(n $op y)),
+                Some(Operands::Float(n, y)) => return Ok(Value::Bool(n $op y)),
+                None => {}
+            }
 
+            match x {
                 Value::Str(s) => {
                     if let Value::Str(y) = other {
                         Ok(Value::Bool(s $op y))
@@ -21,14 +38,92 @@ macro_rules! declare_boolean_op {
                     }
                 }
 
-                _ => unreachable!()
+                x => invalid_operands_err!("compare", x, other)
             }
         }
     };
 }
 
-declare_boolean_op!(equals, ==);
+declare_boolean_op!(compare_equals, ==);
 declare_boolean_op!(less_than_equals, <=);
 declare_boolean_op!(greater_than_equals, >=);
 declare_boolean_op!(greater_than, >);
-declare_boolean_op!(less_than, <);
\ No newline at end of file
+declare_boolean_op!(less_than, <);
+
+// Unlike the other comparisons, equality is also defined for booleans, void,
+// ranges, arrays, maps and structs. Arrays are equal when their elements are,
+// maps when they have the same keys with equal values in any order, and
+// structs when they have the same type and their fields are equal. Comparing
+// elements or fields that can't be compared, like functions, is still an error.
+pub fn equals(x: Value, other: Value) -> Result<Value, NeesyError> {
+    match (&x, &other) {
+        (Value::Bool(x), Value::Bool(y)) => Ok(Value::Bool(x == y)),
+        (Value::Void, Value::Void) => Ok(Value::Bool(true)),
+        (Value::Range(x), Value::Range(y)) => Ok(Value::Bool(x == y)),
+
+        (Value::Array(x), Value::Array(y)) => {
+            if Rc::ptr_eq(x, y) {
+                return Ok(Value::Bool(true));
+            }
+
+            let (x, y) = (x.borrow(), y.borrow());
+
+            if x.len() != y.len() {
+                return Ok(Value::Bool(false));
+            }
+
+            for (x, y) in x.iter().zip(y.iter()) {
+                if let Value::Bool(false) = equals(x.clone(), y.clone())? {
+                    return Ok(Value::Bool(false));
+                }
+            }
+
+            Ok(Value::Bool(true))
+        }
+
+        (Value::Map(x), Value::Map(y)) => {
+            if Rc::ptr_eq(x, y) {
+                return Ok(Value::Bool(true));
+            }
+
+            let (x, y) = (x.borrow(), y.borrow());
+
+            if x.len() != y.len() {
+                return Ok(Value::Bool(false));
+            }
+
+            for (key, x) in x.iter() {
+                let y = match y.get(key) {
+                    Some(y) => y,
+                    None => return Ok(Value::Bool(false)),
+                };
+
+                if let Value::Bool(false) = equals(x.clone(), y.clone())? {
+                    return Ok(Value::Bool(false));
+                }
+            }
+
+            Ok(Value::Bool(true))
+        }
+
+        (Value::Struct(x_type, x_values), Value::Struct(y_type, y_values)) => {
+            if !Rc::ptr_eq(x_type, y_type) {
+                return Ok(Value::Bool(false));
+            }
+
+            for (x, y) in x_values.borrow().iter().zip(y_values.borrow().iter()) {
+                if let Value::Bool(false) = equals(x.clone(), y.clone())? {
+                    return Ok(Value::Bool(false));
+                }
+            }
+
+            Ok(Value::Bool(true))
+        }
+
+        _ => compare_equals(x, other)
+    }
+}
+
+pub fn not_equals(x: Value, other: Value) -> Result<Value, NeesyError> {
+    equals(x, other)?.not()
+}
@@ -0,0 +1,114 @@
+use crate::evaluator::Value;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// A scope of variables. Function values keep a reference to the environment
+// they were created in, so it lives as long as any closure capturing it.
+//
+// A function stored in the scope it was created in forms a reference cycle
+// with it, so the scope of a call that defines an inner function is never
+// freed. Reference counting can't tell those scopes from the ones a returned
+// closure still needs.
+#[derive(Default)]
+pub struct Environment {
+    variables: HashMap<String, Value>,
+    parent: Option<Rc<RefCell<Environment>>>,
+    // Whether this is the scope of a function call. Assignments don't look
+    // past it, so variables of the enclosing scopes only change with `outer`.
+    is_call: bool,
+}
+
+impl Environment {
+    pub fn new(parent: Option<Rc<RefCell<Environment>>>) -> Self {
+        Environment { variables: HashMap::new(), parent, is_call: false }
+    }
+
+    pub fn for_call(closure: Rc<RefCell<Environment>>) -> Self {
+        Environment { variables: HashMap::new(), parent: Some(closure), is_call: true }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.variables.get(name) {
+            Some(value) => Some(value.clone()),
+            None => match &self.parent {
+                Some(parent) => parent.borrow().get(name),
+                None => None,
+            },
+        }
+    }
+
+    pub fn define(&mut self, name: String, value: Value) {
+        self.variables.insert(name, value);
+    }
+
+    // Updates the innermost scope of the current function call already
+    // defining `name`, defining it in this scope if there's none.
+    pub fn assign(&mut self, name: String, value: Value) {
+        if !self.variables.contains_key(&name) && !self.is_call {
+            if let Some(parent) = &self.parent {
+                if parent.borrow().defines_locally(&name) {
+                    parent.borrow_mut().assign(name, value);
+                    return;
+                }
+            }
+        }
+
+        self.variables.insert(name, value);
+    }
+
+    // Updates `name` in the scopes outside the current function call, which
+    // is how closures change the variables they captured. Returns false if
+    // none of them defines it.
+    pub fn assign_outer(&mut self, name: String, value: Value) -> bool {
+        match &self.parent {
+            Some(parent) if self.is_call => parent.borrow_mut().update(name, value),
+            Some(parent) => parent.borrow_mut().assign_outer(name, value),
+            None => false,
+        }
+    }
+
+    fn update(&mut self, name: String, value: Value) -> bool {
+        if let Some(variable) = self.variables.get_mut(&name) {
+            *variable = value;
+            return true;
+        }
+
+        match &self.parent {
+            Some(parent) => parent.borrow_mut().update(name, value),
+            None => false,
+        }
+    }
+
+    fn defines_locally(&self, name: &str) -> bool {
+        if self.variables.contains_key(name) {
+            return true;
+        }
+
+        match &self.parent {
+            Some(parent) if !self.is_call => parent.borrow().defines_locally(name),
+            _ => false,
+        }
+    }
+
+    pub fn variables(&self) -> &HashMap<String, Value> {
+        &self.variables
+    }
+}
+
+// Closures stored in an environment point back to it, so printing it in full
+// would never end.
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names: Vec<&String> = self.variables.keys().collect();
+        names.sort();
+
+        f.debug_struct("Environment")
+            .field("variables", &names)
+            .field("has_parent", &self.parent.is_some())
+            .finish()
+    }
+}
@@ -1,40 +1,90 @@
+macro_rules! runtime_err {
+    ($code:ident, $span:expr, $message:expr) => {
+        Err($crate::error::NeesyError::runtime(
+            $crate::error::ErrorCode::$code,
+            $message.to_string(),
+            $span
+        ).into())
+    }
+}
+
 macro_rules! not_a_function_err {
-    ($fn_name:ident) => {
-        Err(format!("{} is not a function.", $fn_name))
+    ($span:ident, $fn_name:ident) => {
+        runtime_err!(NotCallable, Some($span.clone()), format!("{} is not a function.", $fn_name))
     }
 }
 
 macro_rules! cannot_find_var_err {
-    ($var_name:ident) => {
-        Err(format!("Cannot find variable {}.", $var_name))
+    ($span:ident, $var_name:ident) => {
+        runtime_err!(UndefinedVariable, Some($span.clone()), format!("Cannot find variable {}.", $var_name))
     }
 }
 
 macro_rules! cannot_assign_void_to_var_err {
-    ($var_name:ident) => {
-        Err(format!("Cannot assign void to {}.", $var_name))
+    ($span:ident, $var_name:ident) => {
+        runtime_err!(VoidAssignment, Some($span.clone()), format!("Cannot assign void to {}.", $var_name))
     }
 }
 
 macro_rules! cannot_assign_to_builtin_err {
-    ($var_name:ident) => {
-        Err(format!("Can't assign a value to {} builtin function.", $var_name))
+    ($span:ident, $var_name:ident) => {
+        runtime_err!(
+            BuiltinAssignment, 
+            Some($span.clone()), 
+            format!("Can't assign a value to {} builtin function.", $var_name)
+        )
     }
 }
 
 macro_rules! not_enough_params_err {
-    ($name:ident, $param_count:ident, $actual_params:ident) => {
-        Err(format!(
+    ($span:ident, $name:ident, $param_count:ident, $actual_params:ident) => {
+        runtime_err!(WrongArgumentCount, Some($span.clone()), format!(
             "{} requires {} param(s), {} given",
             $name,
             $param_count,
             $actual_params.len()
-        ));    
+        ))
+    }
+}
+
+macro_rules! condition_not_bool_err {
+    ($span:expr) => {
+        runtime_err!(TypeMismatch, Some($span.clone()), "Condition must be a boolean.")
+    }
+}
+
+macro_rules! return_outside_function_err {
+    ($span:ident) => {
+        runtime_err!(ReturnOutsideFunction, Some($span), "return used outside of a function.")
+    }
+}
+
+macro_rules! loop_control_outside_loop_err {
+    ($span:ident) => {
+        runtime_err!(LoopControlOutsideLoop, Some($span), "break and continue can only be used inside a loop.")
     }
 }
 
 macro_rules! invalid_operands_err {
-    ($op_name:tt, $x:ident, $y:ident) => {
-        Err(format!("Cannot {} {:?} with {:?}", $op_name, $x, $y))
+    ($op_name:tt, $x:expr, $y:expr) => {
+        runtime_err!(InvalidOperands, None, format!("Cannot {} {:?} with {:?}", $op_name, $x, $y))
+    };
+}
+
+macro_rules! invalid_operand_err {
+    ($op_name:tt, $x:ident) => {
+        runtime_err!(InvalidOperands, None, format!("Cannot {} {:?}", $op_name, $x))
     };
-}
\ No newline at end of file
+}
+
+macro_rules! unknown_field_err {
+    ($span:ident, $struct_type:expr, $field:ident) => {
+        runtime_err!(UnknownField, Some($span.clone()), format!("{} has no field named {}.", $struct_type.name, $field))
+    };
+}
+
+macro_rules! not_a_struct_err {
+    ($span:ident, $value:ident, $field:ident) => {
+        runtime_err!(TypeMismatch, Some($span.clone()), format!("Cannot access field {} of {:?}.", $field, $value))
+    };
+}
@@ -0,0 +1,76 @@
+use crate::error::NeesyError;
+use crate::evaluator::Value;
+
+use linked_hash_map::LinkedHashMap;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use rust_decimal::Decimal;
+
+use std::fmt;
+
+// The values that can be used as map keys. Floats are left out since they
+// aren't equal to themselves when NaN, and arrays, maps and functions since
+// they're mutable or have no meaningful equality.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Int(i64),
+    BigInt(BigInt),
+    Decimal(Decimal),
+    Bool(bool),
+    Str(String),
+}
+
+impl MapKey {
+    pub fn new(value: &Value) -> Result<MapKey, NeesyError> {
+        match value {
+            Value::Int(n) => Ok(MapKey::Int(*n)),
+
+            // Big integers that fit in an integer are the same key as it.
+            Value::BigInt(n) => match n.to_i64() {
+                Some(n) => Ok(MapKey::Int(n)),
+                None => Ok(MapKey::BigInt(n.clone())),
+            },
+
+            // So are decimals holding a whole number, since they're equal to it.
+            Value::Decimal(n) if n.fract().is_zero() => match (n.to_i64(), n.to_i128()) {
+                (Some(n), _) => Ok(MapKey::Int(n)),
+                (None, Some(n)) => Ok(MapKey::BigInt(BigInt::from(n))),
+                (None, None) => Ok(MapKey::Decimal(n.normalize())),
+            },
+
+            Value::Decimal(n) => Ok(MapKey::Decimal(n.normalize())),
+            Value::Bool(b) => Ok(MapKey::Bool(*b)),
+            Value::Str(s) => Ok(MapKey::Str(s.clone())),
+
+            x => runtime_err!(TypeMismatch, None, format!("{:?} cannot be used as a map key", x))
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Int(n) => Value::Int(*n),
+            MapKey::BigInt(n) => Value::BigInt(n.clone()),
+            MapKey::Decimal(n) => Value::Decimal(*n),
+            MapKey::Bool(b) => Value::Bool(*b),
+            MapKey::Str(s) => Value::Str(s.clone()),
+        }
+    }
+}
+
+// Sets `key` in `map`. Unlike `LinkedHashMap::insert`, updating a key keeps
+// it at its position, so maps iterate in the order keys were first added.
+pub fn set(map: &mut LinkedHashMap<MapKey, Value>, key: MapKey, value: Value) {
+    match map.get_mut(&key) {
+        Some(entry) => *entry = value,
+        None => { map.insert(key, value); },
+    }
+}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapKey::Str(s) => write!(f, "{:?}", s),
+            _ => write!(f, "{}", self.to_value()),
+        }
+    }
+}
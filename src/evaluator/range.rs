@@ -0,0 +1,61 @@
+use std::fmt;
+
+// An integer range, `start..end` or `start..=end`, counting by `step` (down
+// when it's negative). Its numbers are only produced while iterating, so
+// creating a range costs the same whatever its size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    pub fn new(start: i64, end: i64, step: i64, inclusive: bool) -> Self {
+        Range { start, end, step, inclusive }
+    }
+
+    pub fn iter(&self) -> RangeIter {
+        RangeIter { range: *self, next: Some(self.start) }
+    }
+
+    fn includes(&self, n: i64) -> bool {
+        match (self.step > 0, self.inclusive) {
+            (true, false) => n < self.end,
+            (true, true) => n <= self.end,
+            (false, false) => n > self.end,
+            (false, true) => n >= self.end,
+        }
+    }
+}
+
+pub struct RangeIter {
+    range: Range,
+    next: Option<i64>,
+}
+
+impl Iterator for RangeIter {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        let n = self.next.filter(|n| self.range.includes(*n))?;
+
+        // Stepping past the largest (or smallest) integer ends the range.
+        self.next = n.checked_add(self.range.step);
+
+        Some(n)
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}..{}{}", self.start, if self.inclusive { "=" } else { "" }, self.end)?;
+
+        if self.step != 1 {
+            write!(f, " by {}", self.step)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+// A type declared with `struct Name { field, ... }`. Instances keep their
+// field values in the order of `fields`.
+#[derive(Debug)]
+pub struct StructType {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl StructType {
+    pub fn new(name: String, fields: Vec<String>) -> Self {
+        StructType { name, fields }
+    }
+
+    pub fn field_index(&self, field: &str) -> Option<usize> {
+        self.fields.iter().position(|name| name == field)
+    }
+}
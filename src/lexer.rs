@@ -2,7 +2,12 @@
 pub enum Operator {
     Add, Sub, Mul, Div, Not,
 
+    Mod, FloorDiv, Pow,
+
+    And, Or,
+
     Equals, 
+    NotEquals,
     
     LessThan, 
     LessThanOrEquals, 
@@ -11,41 +16,84 @@ pub enum Operator {
     GreaterThanOrEquals,
 }
 
+use crate::error::{ErrorCode, NeesyError};
+use crate::span::{Position, Span, Spanned};
+
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
+
+use std::rc::Rc;
+use std::str::FromStr;
+
 pub struct Lexer {
     chars: Vec<char>,
-    index: usize 
+    index: usize,
+
+    file: Rc<str>,
+    position: Position,
 }
 
+// A piece of an interpolated string: either text or the tokens of a `${...}`.
+#[derive(Debug, PartialEq)]
+pub enum StringPart {
+    Literal(String),
+    Code(Vec<Spanned<Token>>),
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq)]
 pub enum Token {
+    Int(i64),
+    BigInt(BigInt),
+    Decimal(Decimal),
     Num(f64),
     Str(String),
+    InterpolatedStr(Vec<StringPart>),
     Id(String),
 
     Op(Operator),
 
+    // The text of a `///` comment, up to the end of the line.
+    DocComment(String),
+
     Void,
 
     VBar,
 
     RBrace,
     LBrace,
+    // `#{`, opening a map literal.
+    HashRBrace,
 
     True, 
     False,
 
+    Struct,
+    Match,
+
     While,
+    For,
+    In,
+    By,
+    Break,
+    Continue,
+    Return,
+    Outer,
 
     RParenthesis,
     LParenthesis,
 
     RBracket,
-    LBracket, Not,
+    LBracket,
 
     Colon,
     Comma,
+    Dot,
+    DotDot,
+    DotDotEq,
 
     Assign,
+    FatArrow,
     
     If, 
     Else,
@@ -54,33 +102,82 @@ pub enum Token {
 }
 
 fn is_whitespace(c : char) -> bool {
-    match c {
-        ' ' | '\n' | '\r' | '\t' => true, 
-        _ => false
-    }
+    matches!(c, ' ' | '\n' | '\r' | '\t')
 }
 
 fn is_operator(c : char) -> bool {
-    match c {
-        '+' | '-' | '*' | '=' | '/' | '<' | '>' => true,
-        _ => false
+    matches!(c, '+' | '-' | '*' | '=' | '/' | '%' | '<' | '>' | '!')
+}
+
+fn operator_token(operator: &str) -> Option<Token> {
+    match operator {
+        "+" => Some(Token::Op(Operator::Add)),
+        "-" => Some(Token::Op(Operator::Sub)),
+        "*" => Some(Token::Op(Operator::Mul)),
+        "/" => Some(Token::Op(Operator::Div)),
+        "!" => Some(Token::Op(Operator::Not)),
+
+        "%"  => Some(Token::Op(Operator::Mod)),
+        "**" => Some(Token::Op(Operator::Pow)),
+
+        "==" => Some(Token::Op(Operator::Equals)),
+        "!=" => Some(Token::Op(Operator::NotEquals)),
+        ">=" => Some(Token::Op(Operator::GreaterThanOrEquals)),
+        "<=" => Some(Token::Op(Operator::LessThanOrEquals)),
+        ">"  => Some(Token::Op(Operator::GreaterThan)),
+        "<"  => Some(Token::Op(Operator::LessThan)),
+
+        "<-" => Some(Token::Assign),
+        "=>" => Some(Token::FatArrow),
+
+        _ => None
     }
 }
 
 impl Lexer {
-    pub fn new(buf: String) -> Self {
-        Lexer { chars: buf.chars().collect(), index : 0 }
+    pub fn new(buf: String, file: &str) -> Self {
+        Lexer { 
+            chars: buf.chars().collect(), 
+            index : 0, 
+            file: Rc::from(file), 
+            position: Position::start() 
+        }
     }
     
     fn step(&mut self) {
+        if self.current() == Some('\n') {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+
         self.index += 1;
     }
 
+    fn span_from(&self, start: Position) -> Span {
+        Span::new(self.file.clone(), start, self.position)
+    }
+
+    fn error<T>(&self, code: ErrorCode, start: Position, message: String) -> Result<T, NeesyError> {
+        Err(NeesyError::lex(code, message, Some(self.span_from(start))))
+    }
+
     fn current(&self) -> Option<char> {
-        match self.chars.get(self.index) {
-            Some(c) => Some(*c) ,
-            None => None
-        }
+        self.chars.get(self.index).copied()
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.index + offset).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(i, c)| self.peek(i) == Some(c))
+    }
+
+    // `///` starts a doc comment, but `////` and longer are plain comments.
+    fn at_doc_comment(&self) -> bool {
+        self.starts_with("///") && !self.starts_with("////")
     }
 
     fn read_literal(&mut self) -> Token {
@@ -89,7 +186,7 @@ impl Lexer {
         while let Some(c) = self.current() {
             match c {
                 _ if is_operator(c) || is_whitespace(c) => break,
-                '(' | ')' | ',' | '|' | '{' | '}' | '[' | ']' | ';' => break,
+                '(' | ')' | ',' | '.' | '|' | '{' | '}' | '[' | ']' | ';' => break,
                 _ => literal.push(c)
             }
 
@@ -102,70 +199,214 @@ impl Lexer {
             "void" => Token::Void,
             "true" => Token::True,
             "false" => Token::False,
+            "not" => Token::Op(Operator::Not),
+            "and" => Token::Op(Operator::And),
+            "or" => Token::Op(Operator::Or),
+            // Floor division is a keyword rather than `//`, which is kept
+            // free for line comments.
+            "div" => Token::Op(Operator::FloorDiv),
+            "struct" => Token::Struct,
+            "match" => Token::Match,
             "while" => Token::While,
+            "for" => Token::For,
+            "in" => Token::In,
+            "by" => Token::By,
+            "break" => Token::Break,
+            "continue" => Token::Continue,
+            "return" => Token::Return,
+            "outer" => Token::Outer,
             _ => Token::Id(literal)
         }
     }
 
-    fn read_operator(&mut self) -> Result<Token, String> {
+    // Reads the longest operator starting at the current character, so that
+    // e.g. `x <- -1` isn't read as a single `<--` operator.
+    fn read_operator(&mut self) -> Result<Token, NeesyError> {
+        let start = self.position;
         let mut operator = String::new();
+        let mut longest : Option<(Token, usize)> = None;
 
-        while let Some(c) = self.current() {
-            if !is_operator(c) { break; }
-            operator.push(c);
-            
-            self.step()
-        }
+        for c in self.chars[self.index..].iter() {
+            if !is_operator(*c) { break; }
+            operator.push(*c);
 
-        match &*operator {
-            "+" => Ok(Token::Op(Operator::Add)),
-            "-" => Ok(Token::Op(Operator::Sub)),
-            "*" => Ok(Token::Op(Operator::Mul)),
-            "/" => Ok(Token::Op(Operator::Div)),
+            if let Some(token) = operator_token(&operator) {
+                longest = Some((token, operator.len()));
+            }
+        }
 
-            "==" => Ok(Token::Op(Operator::Equals)),
-            ">=" => Ok(Token::Op(Operator::GreaterThanOrEquals)),
-            "<=" => Ok(Token::Op(Operator::LessThanOrEquals)),
-            ">"  => Ok(Token::Op(Operator::GreaterThan)),
-            "<"  => Ok(Token::Op(Operator::LessThan)),
+        match longest {
+            Some((token, length)) => {
+                for _ in 0..length { self.step(); }
+                Ok(token)
+            },
 
-            "<-" => Ok(Token::Assign),
-            
-            _ => Err(format!("Unknown operator: {}", operator))
+            None => {
+                for _ in 0..operator.len() { self.step(); }
+                self.error(ErrorCode::UnknownOperator, start, format!("Unknown operator: {}", operator))
+            }
         }
     }
 
-    fn read_number(&mut self) -> Result<Token, String> {
-        let mut number       = 0.0;
-        let mut decimal_part = false;
-        let mut e : i64      = 0;
+    // Reads a run of digits in `radix`, which may be separated by `_`. The
+    // separators are dropped from the result.
+    fn read_digits(&mut self, radix: u32, start: Position) -> Result<String, NeesyError> {
+        let mut digits = String::new();
+        let mut last = None;
 
         while let Some(c) = self.current() {
             match c {
-                '0'..='9' => {
-                    let v =  (c as u8 - b'0') as f64;
-                    number = (number * 10.0) + v;
-                    if decimal_part { e-=1; }
-                },
-                '.' => {
-                    if decimal_part {
-                        return Err(format!("Unexpected . at {}", self.index - 1))
-                    }
-
-                    decimal_part = true 
-                },
+                '_' if last.is_some() => {},
+                _ if c.is_digit(radix) => digits.push(c),
                 _ => break
             }
 
+            last = Some(c);
             self.step()
         }
 
-        number *= (10.0 as f64).powf(e as f64);
-        if number.is_infinite() {
-            return Err(format!("Too big number at {}", self.index - 1))
+        if last == Some('_') {
+            return self.error(ErrorCode::InvalidNumber, start, "Digit separators must be followed by a digit".to_string())
+        }
+
+        Ok(digits)
+    }
+
+    // Numbers can't be directly followed by letters or digits of a larger radix,
+    // e.g. `0b102` or `12px`.
+    fn check_number_end(&mut self, start: Position) -> Result<(), NeesyError> {
+        match self.current() {
+            Some(c) if c.is_alphanumeric() || c == '_' => {
+                self.step();
+                self.error(ErrorCode::InvalidNumber, start, format!("Invalid digit {:?} in number", c))
+            }
+
+            _ => Ok(())
+        }
+    }
+
+    fn read_radix_number(&mut self, radix: u32, start: Position) -> Result<Token, NeesyError> {
+        self.step();
+        self.step();
+
+        let digits = self.read_digits(radix, start)?;
+
+        let is_big = self.current() == Some('n');
+        if is_big {
+            self.step();
+        }
+
+        self.check_number_end(start)?;
+
+        if digits.is_empty() {
+            return self.error(ErrorCode::InvalidNumber, start, "Missing digits after the radix prefix".to_string())
+        }
+
+        if is_big {
+            return match BigInt::parse_bytes(digits.as_bytes(), radix) {
+                Some(number) => Ok(Token::BigInt(number)),
+                None => self.error(ErrorCode::InvalidNumber, start, format!("Invalid number {}", digits)),
+            };
+        }
+
+        match i64::from_str_radix(&digits, radix) {
+            Ok(number) => Ok(Token::Int(number)),
+            Err(_) => self.error(ErrorCode::InvalidNumber, start, "Too big number, add an `n` suffix for a big integer".to_string()),
+        }
+    }
+
+    // Reads a decimal number such as `12`, `1_000.5` or `1.5e-3`, leaving its
+    // conversion to the standard float parser so that it's correctly rounded.
+    // An `n` suffix makes an integer a big integer, and a `d` suffix makes any
+    // number an exact decimal.
+    fn read_number(&mut self) -> Result<Token, NeesyError> {
+        let start = self.position;
+
+        if self.current() == Some('0') {
+            match self.peek(1) {
+                Some('x') => return self.read_radix_number(16, start),
+                Some('o') => return self.read_radix_number(8, start),
+                Some('b') => return self.read_radix_number(2, start),
+                _ => {}
+            }
+        }
+
+        let mut number = self.read_digits(10, start)?;
+        let mut is_float = false;
+        let mut has_exponent = false;
+
+        // A `.` only belongs to the number when a digit follows it.
+        if self.current() == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.step();
+            is_float = true;
+            number.push('.');
+            number.push_str(&self.read_digits(10, start)?);
+        }
+
+        if number.is_empty() {
+            self.step();
+            return self.error(ErrorCode::InvalidNumber, start, "Unexpected .".to_string())
+        }
+
+        if let Some('e') | Some('E') = self.current() {
+            self.step();
+            is_float = true;
+            has_exponent = true;
+            number.push('e');
+
+            if let Some(sign @ '+') | Some(sign @ '-') = self.current() {
+                self.step();
+                number.push(sign);
+            }
+
+            let exponent = self.read_digits(10, start)?;
+            if exponent.is_empty() {
+                return self.error(ErrorCode::InvalidNumber, start, "Missing exponent digits".to_string())
+            }
+
+            number.push_str(&exponent);
+        }
+
+        let suffix = match self.current() {
+            Some('n') if !is_float => Some('n'),
+            Some('d') => Some('d'),
+            _ => None
+        };
+
+        if suffix.is_some() {
+            self.step();
+        }
+
+        self.check_number_end(start)?;
+
+        if suffix == Some('n') {
+            return match BigInt::from_str(&number) {
+                Ok(number) => Ok(Token::BigInt(number)),
+                Err(_) => self.error(ErrorCode::InvalidNumber, start, format!("Invalid number {}", number)),
+            };
+        }
+
+        if suffix == Some('d') {
+            let decimal = if has_exponent { Decimal::from_scientific(&number) } else { Decimal::from_str_exact(&number) };
+
+            return match decimal {
+                Ok(number) => Ok(Token::Decimal(number)),
+                Err(_) => self.error(ErrorCode::InvalidNumber, start, "Decimal out of range".to_string()),
+            };
+        }
+
+        if !is_float {
+            return match number.parse::<i64>() {
+                Ok(number) => Ok(Token::Int(number)),
+                Err(_) => self.error(ErrorCode::InvalidNumber, start, "Too big number, add an `n` suffix for a big integer".to_string()),
+            };
+        }
+
+        match number.parse::<f64>() {
+            Ok(number) if number.is_infinite() => self.error(ErrorCode::InvalidNumber, start, "Too big number".to_string()),
+            Ok(number) => Ok(Token::Num(number)),
+            Err(_) => self.error(ErrorCode::InvalidNumber, start, format!("Invalid number {}", number)),
         }
-        
-        Ok(Token::Num(number))
     }
 
     fn skip_whitespaces(&mut self) {
@@ -175,15 +416,102 @@ impl Lexer {
         }
     }
 
-    fn read_string(&mut self) -> Result<Token, String> {
+    fn skip_line(&mut self) {
+        while let Some(c) = self.current() {
+            if c == '\n' { break; }
+            self.step();
+        }
+    }
+
+    // Skips a `/* */` comment, which may contain other block comments.
+    fn skip_block_comment(&mut self) -> Result<(), NeesyError> {
+        let start = self.position;
+        let mut depth = 0;
+
+        loop {
+            if self.starts_with("/*") {
+                depth += 1;
+                self.step();
+            } else if self.starts_with("*/") {
+                depth -= 1;
+                self.step();
+            } else if self.current().is_none() {
+                return self.error(ErrorCode::UnterminatedComment, start, "Block comment not terminated".to_string());
+            }
+
+            self.step();
+
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // Skips whitespaces and comments, except doc comments which are tokens.
+    fn skip_trivia(&mut self) -> Result<(), NeesyError> {
+        loop {
+            self.skip_whitespaces();
+
+            if self.starts_with("//") && !self.at_doc_comment() {
+                self.skip_line();
+            } else if self.starts_with("/*") {
+                self.skip_block_comment()?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read_doc_comment(&mut self) -> Token {
+        for _ in 0..3 { self.step(); }
+
+        let mut text = String::new();
+
+        while let Some(c) = self.current() {
+            match c {
+                '\n' => break,
+                '\r' if self.peek(1) == Some('\n') => break,
+                _ => text.push(c)
+            }
+
+            self.step()
+        }
+
+        Token::DocComment(text)
+    }
+
+    fn read_string(&mut self) -> Result<Token, NeesyError> {
+        let start = self.position;
         self.step();
         let mut string = String::new();
+        let mut parts : Vec<StringPart> = vec![];
         
         while let Some(c) = self.current() {
             match c {
                 '"' => { 
                     self.step(); 
-                    return Ok(Token::Str(string)); 
+
+                    if parts.is_empty() {
+                        return Ok(Token::Str(string));
+                    }
+
+                    if !string.is_empty() {
+                        parts.push(StringPart::Literal(string));
+                    }
+
+                    return Ok(Token::InterpolatedStr(parts));
+                },
+                '\\' => {
+                    string.push(self.read_escape()?);
+                    continue;
+                },
+                '$' if self.peek(1) == Some('{') => {
+                    if !string.is_empty() {
+                        parts.push(StringPart::Literal(std::mem::take(&mut string)));
+                    }
+
+                    parts.push(StringPart::Code(self.read_interpolation(start)?));
+                    continue;
                 },
                 _ => string.push(c)
             }
@@ -191,10 +519,137 @@ impl Lexer {
             self.step()
         }
 
-        Err(format!("String not terminated at {}", self.index - 1))
+        self.error(ErrorCode::UnterminatedString, start, "String not terminated".to_string())
+    }
+
+    // Lexes the code of a `${...}` up to its closing `}`. Strings inside it
+    // are lexed as usual, so they can hold `}` or interpolations of their own.
+    fn read_interpolation(&mut self, string_start: Position) -> Result<Vec<Spanned<Token>>, NeesyError> {
+        self.step();
+        self.step();
+
+        let mut tokens : Vec<Spanned<Token>> = vec![];
+        let mut depth = 0;
+
+        loop {
+            let token = match self.next()? {
+                Some(token) => token,
+                None => return self.error(ErrorCode::UnterminatedString, string_start, "String not terminated".to_string()),
+            };
+
+            match token.node {
+                Token::RBrace | Token::HashRBrace => depth += 1,
+                Token::LBrace if depth == 0 => return Ok(tokens),
+                Token::LBrace => depth -= 1,
+                _ => {}
+            }
+
+            tokens.push(token);
+        }
     }
 
-    fn parse_token(&mut self) -> Result<Token, String> {
+    // Reads an escape sequence starting at the current `\`.
+    fn read_escape(&mut self) -> Result<char, NeesyError> {
+        let start = self.position;
+        self.step();
+
+        let c = match self.current() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('$') => '$',
+
+            Some('u') => return self.read_unicode_escape(start),
+
+            Some(c) => {
+                self.step();
+                return self.error(ErrorCode::InvalidEscape, start, format!("Unknown escape sequence: \\{}", c));
+            }
+
+            None => return self.error(ErrorCode::UnterminatedString, start, "String not terminated".to_string()),
+        };
+
+        self.step();
+        Ok(c)
+    }
+
+    // Reads the `u{...}` part of a `\u{...}` escape, holding 1 to 6 hex digits.
+    fn read_unicode_escape(&mut self, start: Position) -> Result<char, NeesyError> {
+        self.step();
+
+        if self.current() != Some('{') {
+            return self.error(ErrorCode::InvalidEscape, start, "Expected { after \\u".to_string());
+        }
+
+        self.step();
+        let mut digits = String::new();
+
+        while let Some(c) = self.current() {
+            self.step();
+
+            match c {
+                '}' => break,
+                _ if c.is_ascii_hexdigit() && digits.len() < 6 => digits.push(c),
+                _ => return self.error(ErrorCode::InvalidEscape, start, "Unicode escapes must be \\u{...} with 1 to 6 hex digits".to_string()),
+            }
+        }
+
+        let code_point = match u32::from_str_radix(&digits, 16) {
+            Ok(code_point) => code_point,
+            Err(_) => return self.error(ErrorCode::InvalidEscape, start, "Unicode escapes must be \\u{...} with 1 to 6 hex digits".to_string()),
+        };
+
+        match std::char::from_u32(code_point) {
+            Some(c) => Ok(c),
+            None => self.error(ErrorCode::InvalidEscape, start, format!("{:X} is not a valid unicode code point", code_point)),
+        }
+    }
+
+    // Whether a raw string (`r"..."`, `r#"..."#`, ...) starts at the current `r`.
+    fn at_raw_string(&self) -> bool {
+        let mut offset = 1;
+
+        while self.peek(offset) == Some('#') {
+            offset += 1;
+        }
+
+        self.peek(offset) == Some('"')
+    }
+
+    // Reads a raw string, where backslashes have no special meaning. It ends
+    // at the first `"` followed by as many `#` as there were after the `r`.
+    fn read_raw_string(&mut self) -> Result<Token, NeesyError> {
+        let start = self.position;
+        self.step();
+
+        let mut hashes = 0;
+        while self.current() == Some('#') {
+            hashes += 1;
+            self.step();
+        }
+
+        self.step();
+
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        let mut string = String::new();
+
+        while let Some(c) = self.current() {
+            if self.starts_with(&terminator) {
+                for _ in 0..terminator.len() { self.step(); }
+                return Ok(Token::Str(string));
+            }
+
+            string.push(c);
+            self.step()
+        }
+
+        self.error(ErrorCode::UnterminatedString, start, "String not terminated".to_string())
+    }
+
+    fn parse_token(&mut self) -> Result<Token, NeesyError> {
         let c = self.chars[self.index];
 
         match c {
@@ -204,19 +659,26 @@ impl Lexer {
             '[' => { self.step(); Ok(Token::RBracket) },
             ']' => { self.step(); Ok(Token::LBracket) },
             '{' => { self.step(); Ok(Token::RBrace) },
+            '#' if self.peek(1) == Some('{') => { self.step(); self.step(); Ok(Token::HashRBrace) },
             '}' => { self.step(); Ok(Token::LBrace) },
             '|' => { self.step(); Ok(Token::VBar) },
             ',' => { self.step(); Ok(Token::Comma) },
             ';' => { self.step(); Ok(Token::EOS) }
             '"' => self.read_string(),
-            '0'..='9' | '.' => self.read_number(),
+            'r' if self.at_raw_string() => self.read_raw_string(),
+            '0'..='9' => self.read_number(),
+            '.' if self.peek(1).is_some_and(|c| c.is_ascii_digit()) => self.read_number(),
+            '.' if self.starts_with("..=") => { self.step(); self.step(); self.step(); Ok(Token::DotDotEq) },
+            '.' if self.starts_with("..") => { self.step(); self.step(); Ok(Token::DotDot) },
+            '.' => { self.step(); Ok(Token::Dot) },
+            '/' if self.at_doc_comment() => Ok(self.read_doc_comment()),
             _ if is_operator(c) => self.read_operator(),
             _ => Ok(self.read_literal())
         }
     }
 
-    pub fn collect(&mut self) -> Result<Vec<Token>, String> {
-        let mut tokens : Vec<Token> = vec![]; 
+    pub fn collect(&mut self) -> Result<Vec<Spanned<Token>>, NeesyError> {
+        let mut tokens : Vec<Spanned<Token>> = vec![]; 
 
         loop {
             match self.next() {
@@ -235,13 +697,16 @@ impl Lexer {
         Ok(tokens)
     }
 
-    pub fn next(&mut self) -> Result<Option<Token>, String> {
-        self.skip_whitespaces();
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<Spanned<Token>>, NeesyError> {
+        self.skip_trivia()?;
+
+        let start = self.position;
 
         match self.chars.get(self.index) {
-            Some(_) => match self.parse_token() {
-                Ok(res) => Ok(Some(res)),
-                Err(err) => Err(err)
+            Some(_) => {
+                let token = self.parse_token()?;
+                Ok(Some(Spanned::new(token, self.span_from(start))))
             },
             None => Ok(None),
         }
@@ -0,0 +1,7 @@
+pub mod lexer;
+pub mod parser;
+pub mod builtin;
+pub mod evaluator;
+pub mod error;
+pub mod diagnostic;
+pub mod span;
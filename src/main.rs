@@ -1,78 +1,107 @@
-mod lexer;
-mod parser;
-mod builtin;
-mod evaluator;
+mod repl;
 
-use parser::Parser;
-use parser::Precedence;
+use neesy::builtin;
 
-use evaluator::Evaluator;
+use neesy::parser::Parser;
 
-use lexer::Lexer;
+use neesy::evaluator::Evaluator;
 
-fn process(buf : String, evaluator: &mut Evaluator) -> Result<(), String> {
-    let mut lexer = Lexer::new(buf);
+use neesy::lexer::Lexer;
 
-    let tokens = match lexer.collect() {
-        Ok(tokens) => tokens,
-        Err(err) => { return Err(err) }
-    };
+use neesy::error::NeesyError;
+
+use neesy::diagnostic::Diagnostic;
+
+use neesy::evaluator::Value;
+
+use std::collections::HashMap;
+
+// Runs `buf` and returns the value of each of its top-level expressions.
+fn process(buf : &str, file: &str, evaluator: &mut Evaluator) -> Result<Vec<Value>, Vec<NeesyError>> {
+    let mut lexer = Lexer::new(buf.to_string(), file);
+
+    let tokens = lexer.collect().map_err(|err| vec![err])?;
     
     let mut parser = Parser::new(tokens.iter().peekable());
+    let (program, errors) = parser.parse_program();
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
     
-    loop {
-        match parser.parse_expression(Precedence::Lowest) {
-            Ok(result) => { 
-                if let Some(expr) = result {
-                    evaluator.evaluate(&expr)?;
-                } 
-                else {
-                    break;
-                }
-            },
-
-            Err(string) => return Err(string)
-        }
-    };
-
-    Ok(())
+    let mut values : Vec<Value> = vec![];
+
+    for expr in &program {
+        values.push(evaluator.evaluate(expr).map_err(|err| vec![err])?);
+    }
+
+    Ok(values)
 }
 
-use std::collections::HashMap;
+// The text of every input run so far, by the file name its spans carry, so
+// errors inside code from an earlier input still quote the right line.
+type Sources = HashMap<String, String>;
+
+fn report(errors: &[NeesyError], sources: &Sources) {
+    let color = std::io::stdout().is_terminal();
+
+    for err in errors {
+        let diagnostic = Diagnostic::from(err);
+        let source = diagnostic.span.as_ref()
+            .and_then(|span| sources.get(&*span.file))
+            .map(String::as_str);
+
+        print!("{}", diagnostic.render(source, color));
+    }
+}
 
-use evaluator::Value;
 
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::IsTerminal;
 
 fn main() {
     let mut builtin_functions: HashMap<String, Value> = HashMap::new();
 
-    builtin_functions.insert(format!("puts_num"), Value::BuiltinFunction(1, builtin::puts_num));
-    builtin_functions.insert(format!("puts_str"), Value::BuiltinFunction(1, builtin::puts_str));
-    builtin_functions.insert(format!("read_line"), Value::BuiltinFunction(0, builtin::read_line));
-    builtin_functions.insert(format!("pow"), Value::BuiltinFunction(2, builtin::math::pow));
-
-    builtin_functions.insert(format!("to_string"), Value::BuiltinFunction(1, builtin::convert::to_string));
-    builtin_functions.insert(format!("to_number"), Value::BuiltinFunction(1, builtin::convert::to_number));
-
-    let mut eval = Evaluator::new(None, &builtin_functions);
-
-    match std::env::args().skip(1).next() {
+    builtin_functions.insert("puts_num".to_string(), Value::BuiltinFunction(1, builtin::puts_num));
+    builtin_functions.insert("puts_str".to_string(), Value::BuiltinFunction(1, builtin::puts_str));
+    builtin_functions.insert("read_line".to_string(), Value::BuiltinFunction(0, builtin::read_line));
+    builtin_functions.insert("pow".to_string(), Value::BuiltinFunction(2, builtin::math::pow));
+    builtin_functions.insert("range".to_string(), Value::BuiltinFunction(2, builtin::math::range));
+
+    builtin_functions.insert("to_string".to_string(), Value::BuiltinFunction(1, builtin::convert::to_string));
+    builtin_functions.insert("to_number".to_string(), Value::BuiltinFunction(1, builtin::convert::to_number));
+    builtin_functions.insert("int".to_string(), Value::BuiltinFunction(1, builtin::convert::int));
+    builtin_functions.insert("float".to_string(), Value::BuiltinFunction(1, builtin::convert::float));
+    builtin_functions.insert("bigint".to_string(), Value::BuiltinFunction(1, builtin::convert::bigint));
+    builtin_functions.insert("decimal".to_string(), Value::BuiltinFunction(1, builtin::convert::decimal));
+    builtin_functions.insert("to_array".to_string(), Value::BuiltinFunction(1, builtin::convert::to_array));
+
+    builtin_functions.insert("keys".to_string(), Value::BuiltinFunction(1, builtin::map::keys));
+    builtin_functions.insert("values".to_string(), Value::BuiltinFunction(1, builtin::map::values));
+    builtin_functions.insert("has".to_string(), Value::BuiltinFunction(2, builtin::map::has));
+    builtin_functions.insert("remove".to_string(), Value::BuiltinFunction(2, builtin::map::remove));
+
+    match std::env::args().nth(1) {
         Some(arg) => {
+            let mut eval = Evaluator::new(&builtin_functions);
+
             let mut buf = String::new();
 
-            let mut file = File::open(arg).unwrap();
+            let mut file = File::open(&arg).unwrap();
             file.read_to_string(&mut buf).unwrap();
 
-            match process(buf, &mut eval) {
-                Ok(()) => {},
+            let result = process(&buf, &arg, &mut eval);
+
+            let mut sources = Sources::new();
+            sources.insert(arg, buf);
 
-                Err(err) => println!("{}", err)
+            if let Err(errors) = result {
+                report(&errors, &sources);
             }
         },
 
-        None => println!("Usage: neesy <source.nsy>")
+        None => repl::run(&builtin_functions)
     }
 }
             
\ No newline at end of file
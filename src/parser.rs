@@ -1,8 +1,12 @@
-use crate::lexer::Token;
+use crate::error::{ErrorCode, NeesyError};
+use crate::lexer::{Operator, StringPart, Token};
+use crate::span::{Span, Spanned};
 
 use core::slice::Iter;
 
 use linked_hash_set::LinkedHashSet;
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
 use std::iter::Peekable;
 
 pub mod operators;
@@ -11,21 +15,76 @@ use operators::*;
 #[derive(PartialOrd, PartialEq, Copy, Clone)]
 pub enum Precedence {
     Lowest,
+    Or,
+    And,
     Equals,
+    Range,
     Add,
     Mul,
     Prefix,
+    Power,
+}
+
+// The left-hand side of a `match` arm.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Binding(String),
+    Literal(Spanned<Expression>),
+    // The patterns for the first elements, and the one for an array of the
+    // others after `..`. Without it the array must have exactly as many elements.
+    Array(Vec<Pattern>, Option<Box<Pattern>>),
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Spanned<Expression>>,
+    pub body: Spanned<Expression>,
+}
+
+// Gives the `///` lines written above a statement to it, if it's an assignment.
+fn attach_doc(statement: Spanned<Expression>, doc: Vec<String>) -> Spanned<Expression> {
+    match statement.node {
+        Expression::Assignment(name, value, None) if !doc.is_empty() => {
+            Spanned::new(Expression::Assignment(name, value, Some(doc.join("\n"))), statement.span)
+        }
+
+        _ => statement,
+    }
+}
+
+// The expression for a token that can be used as a literal pattern.
+fn literal_pattern(token: &Token) -> Option<Expression> {
+    match token {
+        Token::Int(num) => Some(Expression::Int(*num)),
+        Token::BigInt(num) => Some(Expression::BigInt(num.clone())),
+        Token::Decimal(num) => Some(Expression::Decimal(*num)),
+        Token::Num(num) => Some(Expression::Num(*num)),
+        Token::Str(string) => Some(Expression::Str(string.clone())),
+        Token::True => Some(Expression::Bool(true)),
+        Token::False => Some(Expression::Bool(false)),
+        Token::Void => Some(Expression::Void),
+
+        _ => None
+    }
 }
 
 impl InfixOperator {
     fn precedence(&self) -> Precedence {
         match self {
             InfixOperator::Add | InfixOperator::Sub => Precedence::Add,
-            InfixOperator::Mul | InfixOperator::Div => Precedence::Mul,
+            InfixOperator::Mul | InfixOperator::Div |
+            InfixOperator::Mod | InfixOperator::FloorDiv => Precedence::Mul,
+
+            InfixOperator::Pow => Precedence::Power,
 
-            InfixOperator::Equals   | InfixOperator::GreaterThan      | 
+            InfixOperator::Or => Precedence::Or,
+            InfixOperator::And => Precedence::And,
+
+            InfixOperator::Equals   | InfixOperator::NotEquals        |
             InfixOperator::LessThan | InfixOperator::LessThanOrEquals |
-            InfixOperator::GreaterThanOrEquals => Precedence::Equals
+            InfixOperator::GreaterThan | InfixOperator::GreaterThanOrEquals => Precedence::Equals
         }
     }
 }
@@ -35,251 +94,746 @@ pub enum Expression {
     Void,
 
     Id(String),
-    Assignment(String, Box<Expression>),
+    // The last field holds the `///` doc comment written above the assignment.
+    Assignment(String, Box<Spanned<Expression>>, Option<String>),
+    OuterAssignment(String, Box<Spanned<Expression>>),
+    FieldAssignment(Box<Spanned<Expression>>, String, Box<Spanned<Expression>>),
+    IndexAssignment(Box<Spanned<Expression>>, Box<Spanned<Expression>>, Box<Spanned<Expression>>),
+    Int(i64),
+    BigInt(BigInt),
+    Decimal(Decimal),
     Num(f64),
     Str(String),
+    // The parts of an interpolated string, concatenated at runtime.
+    InterpolatedStr(Vec<Spanned<Expression>>),
     Bool(bool),
 
-    Function(LinkedHashSet<String>, Vec<Expression>),
-    FunctionCall(String, Vec<Expression>),
+    Function(LinkedHashSet<String>, Vec<Spanned<Expression>>),
+    Call(Box<Spanned<Expression>>, Vec<Spanned<Expression>>),
+
+    StructDeclaration(String, Vec<String>),
+    FieldAccess(Box<Spanned<Expression>>, String),
+    MethodCall(Box<Spanned<Expression>>, String, Vec<Spanned<Expression>>),
+    Index(Box<Spanned<Expression>>, Box<Spanned<Expression>>),
 
-    Array(Vec<Expression>),
+    Array(Vec<Spanned<Expression>>),
+    Map(Vec<(Spanned<Expression>, Spanned<Expression>)>),
 
-    If(Box<Expression>, Vec<Expression>, Option<Vec<Expression>>),
-    While(Box<Expression>, Vec<Expression>),
-    Return(Box<Expression>),
+    If(Box<Spanned<Expression>>, Vec<Spanned<Expression>>, Option<Vec<Spanned<Expression>>>),
+    While(Box<Spanned<Expression>>, Vec<Spanned<Expression>>),
+    Range(Box<Spanned<Expression>>, Box<Spanned<Expression>>, Option<Box<Spanned<Expression>>>, bool),
+    Match(Box<Spanned<Expression>>, Vec<MatchArm>),
+    For(String, Box<Spanned<Expression>>, Vec<Spanned<Expression>>),
+    Break,
+    Continue,
+    Return(Option<Box<Spanned<Expression>>>),
 
-    Infix(InfixOperator, Box<Expression>, Box<Expression>),
-    Prefix(PrefixOperator, Box<Expression>),
+    Infix(InfixOperator, Box<Spanned<Expression>>, Box<Spanned<Expression>>),
+    Prefix(PrefixOperator, Box<Spanned<Expression>>),
 }
 
 pub struct Parser<'a> {
-    tokens: Peekable<Iter<'a, Token>>,
+    tokens: Peekable<Iter<'a, Spanned<Token>>>,
+    last_span: Option<Span>,
+    errors: Vec<NeesyError>,
+
+    // How many function bodies enclose the current token.
+    function_depth: usize,
+    // How many loops enclose the current token, inside the innermost function.
+    loop_depth: usize,
 }
 
+fn error<T>(code: ErrorCode, span: Option<&Span>, message: String) -> Result<T, NeesyError> {
+    Err(NeesyError::parse(code, message, span.cloned()))
+}
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: Peekable<Iter<'a, Token>>) -> Self {
-        Parser { tokens }
-    }
-
-    fn parse_prefix_expression(&mut self, op: PrefixOperator) -> Result<Expression, String> {
-        match self.parse_expression(Precedence::Prefix) {
-            Ok(opt) => match opt {
-                Some(expr) => Ok(Expression::Prefix(op, Box::new(expr))),
-                None => Err(format!(
-                    "Expected expression after prefix operator {:?}",
-                    op
-                )),
-            },
+    pub fn new(tokens: Peekable<Iter<'a, Spanned<Token>>>) -> Self {
+        Parser { tokens, last_span: None, errors: vec![], function_depth: 0, loop_depth: 0 }
+    }
+
+    // Parses every statement of the input, recovering from syntax errors so
+    // that all of them get reported. The returned AST skips the statements
+    // that failed to parse.
+    pub fn parse_program(&mut self) -> (Vec<Spanned<Expression>>, Vec<NeesyError>) {
+        let mut program : Vec<Spanned<Expression>> = vec![];
+        let mut doc : Vec<String> = vec![];
+
+        loop {
+            match self.tokens.peek().map(|token| &token.node) {
+                Some(Token::EOS) => { self.next_token(); continue }
+                Some(Token::DocComment(text)) => { doc.push(text.clone()); self.next_token(); continue }
+                _ => {}
+            }
+
+            match self.parse_expression(Precedence::Lowest) {
+                Ok(Some(expr)) => program.push(attach_doc(expr, std::mem::take(&mut doc))),
+                Ok(None) => break,
+
+                Err(err) => {
+                    doc.clear();
+                    self.errors.push(err);
+                    self.synchronize(None);
+                }
+            }
+        }
+
+        (program, std::mem::take(&mut self.errors))
+    }
+
+    // Skips tokens until the end of the current statement (consuming the `;`)
+    // or until `closer` is reached (without consuming it). Nested groups are 
+    // skipped as a whole, while unmatched closing tokens are dropped.
+    fn synchronize(&mut self, closer: Option<Token>) {
+        let mut depth = 0;
+
+        while let Some(token) = self.tokens.peek() {
+            match token.node {
+                Token::EOS if depth == 0 => {
+                    self.next_token();
+                    return;
+                }
+
+                Token::RBrace | Token::HashRBrace | Token::RBracket | Token::RParenthesis => depth += 1,
+
+                Token::LBrace | Token::LBracket | Token::LParenthesis => {
+                    if depth == 0 {
+                        if closer.as_ref() == Some(&token.node) {
+                            return;
+                        }
+                    } else {
+                        depth -= 1;
+                    }
+                }
+
+                _ => {}
+            }
+
+            self.next_token();
+        }
+    }
+
+    fn next_token(&mut self) -> Option<&'a Spanned<Token>> {
+        let token = self.tokens.next();
 
-            Err(err) => Err(err),
+        if let Some(token) = token {
+            self.last_span = Some(token.span.clone());
+        }
+
+        token
+    }
+
+    // Span of the upcoming token, or of the last one when the input is over.
+    fn next_span(&mut self) -> Option<Span> {
+        match self.tokens.peek() {
+            Some(token) => Some(token.span.clone()),
+            None => self.last_span.clone(),
+        }
+    }
+
+    fn span_from(&self, start: &Span) -> Span {
+        match &self.last_span {
+            Some(end) => start.to(end),
+            None => start.clone(),
+        }
+    }
+
+    fn parse_prefix_expression(&mut self, op: PrefixOperator, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        match self.parse_expression(Precedence::Prefix)? {
+            Some(expr) => Ok(Spanned::new(
+                Expression::Prefix(op, Box::new(expr)), 
+                self.span_from(start)
+            )),
+            None => error(ErrorCode::ExpectedExpression, Some(start), format!(
+                "Expected expression after prefix operator {:?}",
+                op
+            )),
         }
     }
 
     fn parse_infix_expression(
         &mut self,
-        lhs: Expression,
+        lhs: Spanned<Expression>,
         op: InfixOperator,
         prec: Precedence,
-    ) -> Result<Expression, String> {
-        self.tokens.next();
+    ) -> Result<Spanned<Expression>, NeesyError> {
+        self.next_token();
+
+        match self.parse_expression(prec)? {
+            Some(rhs) => {
+                let span = lhs.span.to(&rhs.span);
+                Ok(Spanned::new(Expression::Infix(op, Box::new(lhs), Box::new(rhs)), span))
+            }
+
+            None => error(ErrorCode::ExpectedExpression, self.next_span().as_ref(), "Expected expression".to_string())
+        }
+    }
+
+    fn parse_while_expression(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let bool_expr = match self.parse_expression(Precedence::Lowest)? {
+            Some(expr) => expr,
+            None => return error(ErrorCode::ExpectedExpression, self.next_span().as_ref(), "Expected expression".to_owned()),
+        };
+
+        self.expect_next(Token::RBrace)?;
+        self.next_token();
+
+        self.loop_depth += 1;
+        let block = self.parse_block();
+        self.loop_depth -= 1;
+
+        Ok(Spanned::new(Expression::While(Box::new(bool_expr), block?), self.span_from(start)))
+    }
+
+    // Parses `for name in iterable { ... }`.
+    fn parse_for_expression(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let name = match self.next_token() {
+            Some(Spanned { node: Token::Id(name), .. }) => name.clone(),
+
+            Some(token) => return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Expected loop variable, got {:?}", token.node)),
+            None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected loop variable, got nothing".to_string()),
+        };
+
+        self.expect_next(Token::In)?;
+        self.next_token();
+
+        let iterable = match self.parse_expression(Precedence::Lowest)? {
+            Some(expr) => expr,
+            None => return error(ErrorCode::ExpectedExpression, self.next_span().as_ref(), "Expected expression".to_owned()),
+        };
+
+        self.expect_next(Token::RBrace)?;
+        self.next_token();
+
+        self.loop_depth += 1;
+        let block = self.parse_block();
+        self.loop_depth -= 1;
+
+        Ok(Spanned::new(Expression::For(name, Box::new(iterable), block?), self.span_from(start)))
+    }
+
+    fn parse_assign(&mut self, lhs: Spanned<Expression>, prec: Precedence) -> Result<Spanned<Expression>, NeesyError> {
+        if let Expression::Id(_) | Expression::FieldAccess(..) | Expression::Index(..) = lhs.node {
+            self.next_token();
+
+            let rhs = match self.parse_expression(prec)? {
+                Some(expr) => expr,
+                None => return error(ErrorCode::ExpectedExpression, self.next_span().as_ref(), "Expected expression".to_string()),
+            };
 
-        match self.parse_expression(prec) {
-            Ok(result) => {
-                if let Some(rhs) = result {
-                    return Ok(Expression::Infix(op, Box::new(lhs), Box::new(rhs)));
+            let span = lhs.span.to(&rhs.span);
+
+            match lhs.node {
+                Expression::FieldAccess(object, field) => {
+                    Ok(Spanned::new(Expression::FieldAssignment(object, field, Box::new(rhs)), span))
+                }
+
+                Expression::Index(container, index) => {
+                    Ok(Spanned::new(Expression::IndexAssignment(container, index, Box::new(rhs)), span))
                 }
 
-                Err(format!("Expected expression"))
+                Expression::Id(id) => Ok(Spanned::new(Expression::Assignment(id, Box::new(rhs), None), span)),
+
+                _ => unreachable!(),
             }
+        } else {
+            error(ErrorCode::InvalidAssignment, Some(&lhs.span), format!("{:?} is not an identifier, a field or an index", lhs.node))
+        }
+    }
+
+    // Parses the `.field` or `.method(args)` following `object`.
+    fn parse_field_access(&mut self, object: Spanned<Expression>) -> Result<Spanned<Expression>, NeesyError> {
+        self.next_token();
 
-            Err(err) => Err(err),
+        match self.next_token() {
+            Some(Spanned { node: Token::Id(field), span }) => {
+                if self.is_next(Token::RParenthesis) {
+                    self.next_token();
+                    let args = self.parse_arguments()?;
+
+                    let span = self.span_from(&object.span);
+                    return Ok(Spanned::new(Expression::MethodCall(Box::new(object), field.clone(), args), span));
+                }
+
+                let span = object.span.to(span);
+                Ok(Spanned::new(Expression::FieldAccess(Box::new(object), field.clone()), span))
+            }
+
+            Some(token) => error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Expected field name, got {:?}", token.node)),
+            None => error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected field name, got nothing".to_string()),
         }
     }
 
-    fn parse_while_expression(&mut self) -> Result<Expression, String> {
-        let bool_expr_opt = self.parse_expression(Precedence::Lowest)?;
+    // Parses the comma separated arguments of a call, after its `(`.
+    fn parse_arguments(&mut self) -> Result<Vec<Spanned<Expression>>, NeesyError> {
+        let mut args : Vec<Spanned<Expression>> = vec![];
 
-        if bool_expr_opt.is_none() {
-            return Err("Expected expression".to_owned());
+        loop {
+            self.skip_doc_comments();
+
+            if self.is_next(Token::LParenthesis) {
+                self.next_token();
+                break;
+            }
+
+            match self.parse_expression(Precedence::Lowest)? {
+                Some(expr) => args.push(expr),
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected ), got nothing".to_string()),
+            }
+
+            self.skip_doc_comments();
+
+            match self.next_token() {
+                Some(Spanned { node: Token::Comma, .. }) => continue,
+                Some(Spanned { node: Token::LParenthesis, .. }) => break,
+
+                Some(token) => return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Expected , or ), got {:?}", token.node)),
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected , or ), got nothing".to_string()),
+            }
         }
 
+        Ok(args)
+    }
+
+    // Parses the `[index]` following `container`.
+    fn parse_index(&mut self, container: Spanned<Expression>) -> Result<Spanned<Expression>, NeesyError> {
+        self.next_token();
+
+        let index = match self.parse_expression(Precedence::Lowest)? {
+            Some(expr) => expr,
+            None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected index, got nothing".to_string()),
+        };
+
+        self.expect_next(Token::LBracket)?;
+        self.next_token();
+
+        let span = self.span_from(&container.span);
+        Ok(Spanned::new(Expression::Index(Box::new(container), Box::new(index)), span))
+    }
+
+    // Parses `match subject { pattern => value, ... }`.
+    fn parse_match(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let subject = match self.parse_expression(Precedence::Lowest)? {
+            Some(expr) => expr,
+            None => return error(ErrorCode::ExpectedExpression, self.next_span().as_ref(), "Expected expression".to_owned()),
+        };
+
         self.expect_next(Token::RBrace)?;
-        self.tokens.next();
+        self.next_token();
 
-        let block = self.parse_block()?;
+        let mut arms: Vec<MatchArm> = Vec::new();
+
+        loop {
+            self.skip_doc_comments();
+
+            if self.is_next(Token::LBrace) {
+                self.next_token();
+                break;
+            }
+
+            let pattern = self.parse_pattern()?;
+
+            let guard = if self.is_next(Token::If) {
+                self.next_token();
+
+                match self.parse_expression(Precedence::Lowest)? {
+                    Some(expr) => Some(expr),
+                    None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected guard, got nothing".to_string()),
+                }
+            } else {
+                None
+            };
+
+            self.expect_next(Token::FatArrow)?;
+            self.next_token();
+
+            let body = match self.parse_expression(Precedence::Lowest)? {
+                Some(expr) => expr,
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected expression, got nothing".to_string()),
+            };
+
+            arms.push(MatchArm { pattern, guard, body });
+            self.skip_doc_comments();
+
+            match self.next_token() {
+                Some(Spanned { node: Token::Comma, .. }) => continue,
+                Some(Spanned { node: Token::LBrace, .. }) => break,
+
+                Some(token) => return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Expected , or }}, got {:?}", token.node)),
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected , or }, got nothing".to_string()),
+            }
+        }
+
+        Ok(Spanned::new(Expression::Match(Box::new(subject), arms), self.span_from(start)))
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, NeesyError> {
+        let token = match self.next_token() {
+            Some(token) => token,
+            None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected pattern, got nothing".to_string()),
+        };
+
+        let span = &token.span;
+
+        let pattern = match &token.node {
+            Token::Id(name) if name == "_" => Pattern::Wildcard,
+            Token::Id(name) => Pattern::Binding(name.clone()),
+
+            Token::RBracket => self.parse_array_pattern()?,
+
+            // Negative numbers are the only prefix expressions allowed.
+            Token::Op(Operator::Sub) => match self.next_token() {
+                Some(Spanned { node, span: number_span }) => match literal_pattern(node) {
+                    Some(number @ Expression::Int(_)) | Some(number @ Expression::BigInt(_)) |
+                    Some(number @ Expression::Decimal(_)) | Some(number @ Expression::Num(_)) => {
+                        let number = Spanned::new(number, number_span.clone());
+                        Pattern::Literal(Spanned::new(Expression::Prefix(PrefixOperator::Negative, Box::new(number)), span.to(number_span)))
+                    }
+
+                    _ => return error(ErrorCode::UnexpectedToken, Some(number_span), format!("Expected number, got {:?}", node)),
+                },
+
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected number, got nothing".to_string()),
+            },
+
+            node => match literal_pattern(node) {
+                Some(literal) => Pattern::Literal(Spanned::new(literal, span.clone())),
+                None => return error(ErrorCode::UnexpectedToken, Some(span), format!("Expected pattern, got {:?}", node)),
+            },
+        };
+
+        Ok(pattern)
+    }
+
+    // Parses the rest of an array pattern, `[first, second, ..rest]`.
+    fn parse_array_pattern(&mut self) -> Result<Pattern, NeesyError> {
+        let mut items: Vec<Pattern> = Vec::new();
+        let mut rest: Option<Box<Pattern>> = None;
 
-        Ok(Expression::While(Box::new(bool_expr_opt.unwrap()), block))
+        loop {
+            if self.is_next(Token::LBracket) {
+                self.next_token();
+                break;
+            }
+
+            if rest.is_some() {
+                let token = self.next_token();
+                return error(ErrorCode::UnexpectedToken, token.map(|token| &token.span), "The rest of an array pattern must come last".to_string());
+            }
+
+            if self.is_next(Token::DotDot) {
+                self.next_token();
+
+                // `..` on its own ignores the remaining elements.
+                rest = Some(Box::new(match self.tokens.peek().map(|token| &token.node) {
+                    Some(Token::Comma) | Some(Token::LBracket) => Pattern::Wildcard,
+                    _ => self.parse_pattern()?,
+                }));
+            } else {
+                items.push(self.parse_pattern()?);
+            }
+
+            match self.tokens.peek() {
+                Some(Spanned { node: Token::Comma, .. }) => { self.next_token(); },
+                Some(Spanned { node: Token::LBracket, .. }) => {},
+
+                Some(token) => return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Expected , or ], got {:?}", token.node)),
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected , or ], got nothing".to_string()),
+            }
+        }
+
+        Ok(Pattern::Array(items, rest))
     }
 
-    fn parse_assign(&mut self, lhs: Expression, prec: Precedence) -> Result<Expression, String> {
-        if let Expression::Id(id) = lhs {
-            self.tokens.next();
-            Ok(Expression::Assignment(
-                id,
-                Box::new(match self.parse_expression(prec)? {
-                    Some(expr) => expr,
-                    None => return Err(format!("Expected expression")),
-                }),
-            ))
+    // Parses the rest of `start..end` or `start..=end`, with an optional
+    // `by step` after it.
+    fn parse_range(&mut self, start: Spanned<Expression>) -> Result<Spanned<Expression>, NeesyError> {
+        let inclusive = self.next_token().is_some_and(|token| token.node == Token::DotDotEq);
+
+        let end = match self.parse_expression(Precedence::Range)? {
+            Some(expr) => expr,
+            None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected end of range, got nothing".to_string()),
+        };
+
+        let step = if self.is_next(Token::By) {
+            self.next_token();
+
+            match self.parse_expression(Precedence::Range)? {
+                Some(expr) => Some(Box::new(expr)),
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected range step, got nothing".to_string()),
+            }
         } else {
-            return Err(format!("{:?} is not an identifier", lhs));
+            None
+        };
+
+        let span = self.span_from(&start.span);
+        Ok(Spanned::new(Expression::Range(Box::new(start), Box::new(end), step, inclusive), span))
+    }
+
+    // Parses `struct Name { field, ... }`.
+    fn parse_struct(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let name = match self.next_token() {
+            Some(Spanned { node: Token::Id(name), .. }) => name.clone(),
+
+            Some(token) => return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Expected struct name, got {:?}", token.node)),
+            None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected struct name, got nothing".to_string()),
+        };
+
+        self.expect_next(Token::RBrace)?;
+        self.next_token();
+
+        let mut fields: Vec<String> = vec![];
+
+        loop {
+            let token = match self.next_token() {
+                Some(token) => token,
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected LBrace, got nothing".to_string()),
+            };
+
+            match &token.node {
+                Token::LBrace => break,
+                Token::EOS | Token::DocComment(_) => continue,
+
+                Token::Id(field) => {
+                    if fields.contains(field) {
+                        return error(ErrorCode::DuplicateField, Some(&token.span), format!("Field {} was already declared", field));
+                    }
+
+                    fields.push(field.clone());
+                }
+
+                _ => return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Expected field name, got {:?}", token.node)),
+            }
+
+            self.skip_doc_comments();
+
+            match self.next_token() {
+                Some(Spanned { node: Token::Comma, .. }) | Some(Spanned { node: Token::EOS, .. }) => {},
+                Some(Spanned { node: Token::LBrace, .. }) => break,
+
+                Some(token) => return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Unexpected {:?} in field list", token.node)),
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected , or LBrace, got nothing".to_string()),
+            }
+        }
+
+        Ok(Spanned::new(Expression::StructDeclaration(name, fields), self.span_from(start)))
+    }
+
+    // Doc comments only mean something before a statement; inside lists and
+    // literals they're skipped like ordinary comments.
+    fn skip_doc_comments(&mut self) {
+        while let Some(Token::DocComment(_)) = self.tokens.peek().map(|token| &token.node) {
+            self.next_token();
         }
     }
 
     fn is_next(&mut self, token: Token) -> bool {
         match self.tokens.peek() {
             Some(next_token) => {
-                if token == **next_token {
-                    true
-                } else {
-                    false
-                }
+                token == next_token.node
             }
 
             None => false,
         }
     }
 
-    fn expect_next(&mut self, token: Token) -> Result<(), String> {
+    fn expect_next(&mut self, token: Token) -> Result<(), NeesyError> {
         match self.tokens.peek() {
             Some(next_token) => {
-                if token == **next_token {
+                if token == next_token.node {
                     Ok(())
                 } else {
-                    Err(format!("Expected {:?}, got {:?}", token, next_token))
+                    error(ErrorCode::UnexpectedToken, Some(&next_token.span), format!("Expected {:?}, got {:?}", token, next_token.node))
                 }
             }
 
-            None => Err(format!("Expected {:?}, got nothing", token)),
+            None => error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), format!("Expected {:?}, got nothing", token)),
         }
     }
 
-    fn parse_function_call(&mut self) -> Result<Expression, String> {
-        if let Some(token) = self.tokens.next() {
-            let f_name = match token {
-                Token::Id(id) => String::from(id),
+    // Parses the expression between `(` and `)`.
+    fn parse_group(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let inner = match self.parse_expression(Precedence::Lowest)? {
+            Some(expr) => expr,
+            None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected ), got nothing".to_string()),
+        };
 
-                _ => return Err(format!("Expected identifier, got {:?}", token))
-            };
+        self.expect_next(Token::LParenthesis)?;
+        self.next_token();
 
-            let mut params : Vec<Expression> = vec![];  
+        Ok(Spanned::new(inner.node, self.span_from(start)))
+    }
 
-            loop {
-                if let Some(next_token) = self.tokens.peek() {
-                    match next_token {
-                        Token::LBracket => { self.tokens.next(); break } , 
-                        Token::EOS => { self.tokens.next(); },  
-                        _ => {}
-                    }
-                } else {
-                    return Err(format!("Expected LBrace, got nothing"));
+    // Parses the callee of a `[callee args]` call. Since the arguments follow
+    // it directly, it can't be a full expression: `[f (x)]` passes `(x)` to `f`
+    // rather than calling `f(x)`. Anything else can still be called by wrapping
+    // it in parentheses.
+    fn parse_callee(&mut self) -> Result<Spanned<Expression>, NeesyError> {
+        let token = match self.next_token() {
+            Some(token) => token,
+            None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected function name, got nothing.".to_owned()),
+        };
+
+        let start = &token.span;
+
+        let mut callee = match &token.node {
+            Token::Id(id) => Spanned::new(Expression::Id(String::from(id)), start.clone()),
+            Token::RBracket => self.parse_function_call(start)?,
+            Token::RParenthesis => self.parse_group(start)?,
+            Token::VBar => self.parse_function(start)?,
+
+            _ => return error(ErrorCode::UnexpectedToken, Some(start), format!("Expected identifier, got {:?}", token.node))
+        };
+
+        while self.is_next(Token::Dot) {
+            callee = self.parse_field_access(callee)?;
+        }
+
+        Ok(callee)
+    }
+
+    fn parse_function_call(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let callee = self.parse_callee()?;
+
+        let mut params : Vec<Spanned<Expression>> = vec![];  
+
+        loop {
+            if let Some(next_token) = self.tokens.peek() {
+                match next_token.node {
+                    Token::LBracket => { self.next_token(); break } , 
+                    Token::EOS => { self.next_token(); },  
+                    Token::DocComment(_) => { self.next_token(); continue },
+                    _ => {}
                 }
-    
-                match self.parse_expression(Precedence::Lowest)? {
-                    Some(expr) => params.push(expr),
-                    None => break,
+            } else {
+                return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected LBrace, got nothing".to_string());
+            }
+
+            match self.parse_expression(Precedence::Lowest) {
+                Ok(Some(expr)) => params.push(expr),
+                Ok(None) => break,
+
+                Err(err) => {
+                    self.errors.push(err);
+                    self.synchronize(Some(Token::LBracket));
                 }
             }
-            
-            return Ok(Expression::FunctionCall(f_name, params))
         }
         
-        Err("Expected function name, got nothing.".to_owned())
+        Ok(Spanned::new(Expression::Call(Box::new(callee), params), self.span_from(start)))
     }
 
-    fn parse_if_expression(&mut self) -> Result<Expression, String> {
-        let bool_expr_opt = self.parse_expression(Precedence::Lowest)?;
-
-        if bool_expr_opt.is_none() {
-            return Err("Expected expression".to_owned());
-        }
+    fn parse_if_expression(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let bool_expr = match self.parse_expression(Precedence::Lowest)? {
+            Some(expr) => expr,
+            None => return error(ErrorCode::ExpectedExpression, self.next_span().as_ref(), "Expected expression".to_owned()),
+        };
 
         self.expect_next(Token::RBrace)?;
-        self.tokens.next();
+        self.next_token();
 
         let block = self.parse_block()?;
         let else_block = if self.is_next(Token::Else) {
-            self.tokens.next();
+            self.next_token();
 
-            self.expect_next(Token::RBrace)?;
-            self.tokens.next();
-    
-            Some(self.parse_block()?)
+            // `else if` is an else block holding just the next `if`, so
+            // chains don't need to be nested by hand.
+            if self.is_next(Token::If) {
+                let if_start = &self.next_token().unwrap().span;
+                Some(vec![self.parse_if_expression(if_start)?])
+            } else {
+                self.expect_next(Token::RBrace)?;
+                self.next_token();
+
+                Some(self.parse_block()?)
+            }
         } else { None };
 
-        Ok(Expression::If(Box::new(bool_expr_opt.unwrap()), block, else_block))
+        Ok(Spanned::new(
+            Expression::If(Box::new(bool_expr), block, else_block),
+            self.span_from(start)
+        ))
     }
 
-    fn parse_block(&mut self) -> Result<Vec<Expression>, String>{
-        let mut expressions : Vec<Expression> = vec![];
+    fn parse_block(&mut self) -> Result<Vec<Spanned<Expression>>, NeesyError>{
+        let mut expressions : Vec<Spanned<Expression>> = vec![];
+        let mut doc : Vec<String> = vec![];
 
         loop {
             if let Some(next_token) = self.tokens.peek() {
-                match next_token {
-                    Token::LBrace => { self.tokens.next(); break } , 
-                    Token::EOS => { self.tokens.next(); }
+                match &next_token.node {
+                    Token::LBrace => { self.next_token(); break } , 
+                    Token::EOS => { self.next_token(); continue }
+                    Token::DocComment(text) => { doc.push(text.clone()); self.next_token(); continue }
                     _ => {}
                 }
             } else {
-                return Err(format!("Expected LBrace, got nothing"));
+                return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected LBrace, got nothing".to_string());
             }
 
-            match self.parse_expression(Precedence::Lowest)? {
-                Some(expr) => expressions.push(expr),
-                None => break,
+            match self.parse_expression(Precedence::Lowest) {
+                Ok(Some(expr)) => expressions.push(attach_doc(expr, std::mem::take(&mut doc))),
+                Ok(None) => break,
+
+                Err(err) => {
+                    doc.clear();
+                    self.errors.push(err);
+                    self.synchronize(Some(Token::LBrace));
+                }
             }
         }
 
         Ok(expressions)
     }
 
-    fn parse_array(&mut self) -> Result<Expression, String> {
-        let mut array: Vec<Expression> = Vec::new();
+    fn parse_array(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let mut array: Vec<Spanned<Expression>> = Vec::new();
         
         loop {
+            self.skip_doc_comments();
+
             array.push(match self.parse_expression(Precedence::Lowest)? {
                 Some(expr) => expr,
 
-                None => return Err(format!("Expected expression"))
+                None => return error(ErrorCode::ExpectedExpression, self.next_span().as_ref(), "Expected expression".to_string())
             });
 
+            self.skip_doc_comments();
+
             match self.tokens.peek() {
                 Some(token) => {
-                    match token {
-                        Token::Colon => break,
-                        Token::EOS => { self.tokens.next(); },
+                    match token.node {
+                        Token::Colon => { self.next_token(); break },
+                        Token::EOS => { self.next_token(); },
                         
-                        _ =>  return Err(format!("Unexpected {:?}", token))
+                        _ =>  return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Unexpected {:?}", token.node))
                     }
                 },
 
-                None => return Err(format!("Unexpected EOS."))
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Unexpected EOS.".to_string())
             }
         }
 
-        Ok(Expression::Array(array))
+        Ok(Spanned::new(Expression::Array(array), self.span_from(start)))
     }  
 
-    fn parse_function(&mut self) -> Result<Expression, String> {
+    fn parse_function(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
         
         // Parse arguments
         let mut parameters: LinkedHashSet<String> = LinkedHashSet::new();
 
         loop {
-            let next_token= self.tokens.next() ;
-            
-            if next_token.is_none() {
-                return Err(format!("Expected function arguments."))
-            }
+            let next_token = match self.next_token() {
+                Some(token) => token,
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected function arguments.".to_string()),
+            };
 
-            parameters.insert(match next_token.unwrap() {
+            parameters.insert(match &next_token.node {
                 Token::Id(id) => { 
                     let n_id = String::from(id);
                     
                     if parameters.contains(&n_id) {
-                        return Err(format!("Parameter {} was already specified", n_id));
+                        return error(ErrorCode::DuplicateParameter, Some(&next_token.span), format!("Parameter {} was already specified", n_id));
                     }
 
                     n_id
@@ -287,11 +841,11 @@ impl<'a> Parser<'a> {
 
                 Token::VBar => { break; }
 
-                _ => return Err(format!("Expected parameter name.")),
+                _ => return error(ErrorCode::UnexpectedToken, Some(&next_token.span), "Expected parameter name.".to_string()),
             });
 
-            if let Some(token) = self.tokens.next() {
-                match token {
+            if let Some(token) = self.next_token() {
+                match token.node {
                     Token::Comma => {
                         continue;
                     }
@@ -299,105 +853,276 @@ impl<'a> Parser<'a> {
                         break;
                     }
 
-                    _ => return Err(format!("Unexpected {:?} in parameter list", token)),
+                    _ => return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Unexpected {:?} in parameter list", token.node)),
                 }
             } else {
-                return Err(format!("Expected , or |, got nothing"));
+                return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected , or |, got nothing".to_string());
             }
         }
 
         // Parse body
 
         self.expect_next(Token::RBrace)?;
-        self.tokens.next();
+        self.next_token();
+
+        // Loops around the function definition can't be controlled from its body.
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+
+        self.function_depth += 1;
+        let body = self.parse_block();
+        self.function_depth -= 1;
 
-        Ok(Expression::Function(parameters, self.parse_block()?))
+        self.loop_depth = loop_depth;
+
+        Ok(Spanned::new(Expression::Function(parameters, body?), self.span_from(start)))
     }
 
-    pub fn parse_expression(&mut self, prec: Precedence) -> Result<Option<Expression>, String> {
-        if let Some(token) = self.tokens.next() {
-            let lhs_opt = match token {
-                Token::Op(op) => {
-                    let p_op = op.to_prefix();
+    fn parse_return(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let value = match self.tokens.peek().map(|token| &token.node) {
+            None | Some(Token::EOS) | Some(Token::LBrace) | Some(Token::Comma) |
+            Some(Token::LParenthesis) | Some(Token::LBracket) | Some(Token::DocComment(_)) => None,
+            _ => self.parse_expression(Precedence::Lowest)?.map(Box::new),
+        };
 
-                    if p_op.is_none() {
-                        return Err(format!("{:?} is not a valid prefix operator", op));
-                    }
+        let span = self.span_from(start);
 
-                    Some(self.parse_prefix_expression(p_op.unwrap())?)
-                }
+        if self.function_depth == 0 {
+            return error(ErrorCode::ReturnOutsideFunction, Some(&span), "return used outside of a function.".to_string());
+        }
+
+        Ok(Spanned::new(Expression::Return(value), span))
+    }
 
-                Token::VBar => Some(self.parse_function()?),
-                Token::RBracket =>  { Some(self.parse_function_call()?) },
-                Token::RBracket => Some(self.parse_function_call()?),         
-                Token::Colon => Some(self.parse_array()?),
+    // Parses `outer name <- value`, which assigns to a variable captured from
+    // outside the current function instead of defining a local one.
+    fn parse_outer_assignment(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let name = match self.next_token() {
+            Some(Spanned { node: Token::Id(name), .. }) => name.clone(),
 
-                Token::While =>Some(self.parse_while_expression()?),
+            Some(token) => return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Expected variable name, got {:?}", token.node)),
+            None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected variable name, got nothing".to_string()),
+        };
 
-                Token::True => Some(Expression::Bool(true)),
-                Token::False => Some(Expression::Bool(false)),
+        self.expect_next(Token::Assign)?;
+        self.next_token();
 
-                Token::Void => Some(Expression::Void),
+        let value = match self.parse_expression(Precedence::Lowest)? {
+            Some(expr) => expr,
+            None => return error(ErrorCode::ExpectedExpression, self.next_span().as_ref(), "Expected expression".to_string()),
+        };
+
+        let span = self.span_from(start);
+
+        if self.function_depth == 0 {
+            return error(ErrorCode::InvalidAssignment, Some(&span), "outer can only be used inside a function.".to_string());
+        }
+
+        Ok(Spanned::new(Expression::OuterAssignment(name, Box::new(value)), span))
+    }
+
+    fn parse_loop_control(&mut self, expression: Expression, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        if self.loop_depth == 0 {
+            return error(
+                ErrorCode::LoopControlOutsideLoop, 
+                Some(start), 
+                "break and continue can only be used inside a loop.".to_string()
+            );
+        }
+
+        Ok(Spanned::new(expression, start.clone()))
+    }
 
-                Token::Num(num) => Some(Expression::Num(*num)),
-                Token::Str(string) => Some(Expression::Str(String::from(string))),
-                Token::Id(id) => Some(Expression::Id(String::from(id))),
+    fn parse_interpolated_string(&mut self, parts: &'a [StringPart], start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let mut expressions : Vec<Spanned<Expression>> = vec![];
 
-                Token::RParenthesis => {
-                    return match self.parse_expression(Precedence::Lowest)? {
-                        Some(expr) => Ok(Some(expr)),
-                        None => Ok(None),
+        for part in parts {
+            match part {
+                StringPart::Literal(string) => {
+                    expressions.push(Spanned::new(Expression::Str(string.clone()), start.clone()))
+                },
+
+                StringPart::Code(tokens) => {
+                    let mut parser = Parser::new(tokens.iter().peekable());
+                    parser.function_depth = self.function_depth;
+                    parser.loop_depth = self.loop_depth;
+
+                    let expression = parser.parse_expression(Precedence::Lowest);
+                    self.errors.append(&mut parser.errors);
+
+                    match expression? {
+                        Some(expr) => expressions.push(expr),
+                        None => return error(ErrorCode::ExpectedExpression, Some(start), "Expected expression inside ${}".to_string()),
                     }
+
+                    if let Some(token) = parser.tokens.peek() {
+                        return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Unexpected {:?}", token.node));
+                    }
+                },
+            }
+        }
+
+        Ok(Spanned::new(Expression::InterpolatedStr(expressions), start.clone()))
+    }
+
+    // Parses the entries of a `#{ key => value; ... }` map literal.
+    fn parse_map(&mut self, start: &Span) -> Result<Spanned<Expression>, NeesyError> {
+        let mut entries: Vec<(Spanned<Expression>, Spanned<Expression>)> = Vec::new();
+
+        loop {
+            match self.tokens.peek() {
+                Some(token) => match token.node {
+                    Token::LBrace => { self.next_token(); break },
+                    Token::EOS | Token::DocComment(_) => { self.next_token(); continue },
+                    _ => {}
                 },
 
-                Token::If => Some(self.parse_if_expression()?),
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Expected LBrace, got nothing".to_string())
+            }
+
+            let key = match self.parse_expression(Precedence::Lowest)? {
+                Some(expr) => expr,
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Unexpected EOS.".to_string())
+            };
 
-                Token::EOS => return self.parse_expression(prec),
+            self.expect_next(Token::FatArrow)?;
+            self.next_token();
 
-                _ => return Err(format!("Expected expression, got {:?}", token)),
+            let value = match self.parse_expression(Precedence::Lowest)? {
+                Some(expr) => expr,
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Unexpected EOS.".to_string())
             };
 
-            if lhs_opt.is_none() {
-                return Ok(None);
+            entries.push((key, value));
+            self.skip_doc_comments();
+
+            match self.tokens.peek() {
+                Some(token) => match token.node {
+                    Token::LBrace | Token::EOS => {},
+                    _ => return error(ErrorCode::UnexpectedToken, Some(&token.span), format!("Expected ; or }}, got {:?}", token.node))
+                },
+
+                None => return error(ErrorCode::UnexpectedEnd, self.last_span.as_ref(), "Unexpected EOS.".to_string())
             }
+        }
 
-            let mut lhs = lhs_opt.unwrap();
+        Ok(Spanned::new(Expression::Map(entries), self.span_from(start)))
+    }
 
-            loop {
-                if let Some(next_token) = self.tokens.peek() {
-                    lhs = match next_token {
-                        Token::EOS | Token::RBrace | Token::LBrace | Token::LBracket | Token::Colon => {
-                            break;
-                        },
+    pub fn parse_expression(&mut self, prec: Precedence) -> Result<Option<Spanned<Expression>>, NeesyError> {
+        // Leave closing tokens and `;` to whoever opened the group or the
+        // statement, so that it's still able to close it after reporting the error.
+        if let Some(token) = self.tokens.peek() {
+            if let Token::LBrace | Token::LBracket | Token::LParenthesis | Token::EOS = token.node {
+                return error(
+                    ErrorCode::ExpectedExpression, 
+                    Some(&token.span), 
+                    format!("Expected expression, got {:?}", token.node)
+                );
+            }
+        }
+
+        if let Some(token) = self.next_token() {
+            let start = &token.span;
+
+            let mut lhs = match &token.node {
+                Token::Op(op) => {
+                    let p_op = match op.to_prefix() {
+                        Some(p_op) => p_op,
+                        None => return error(ErrorCode::UnexpectedToken, Some(start), format!("{:?} is not a valid prefix operator", op)),
+                    };
+
+                    self.parse_prefix_expression(p_op, start)?
+                }
+
+                Token::VBar => self.parse_function(start)?,
+                Token::RBracket => self.parse_function_call(start)?,
+                Token::Colon => self.parse_array(start)?,
+                Token::HashRBrace => self.parse_map(start)?,
+
+                Token::While => self.parse_while_expression(start)?,
+                Token::For => self.parse_for_expression(start)?,
+                Token::Struct => self.parse_struct(start)?,
+                Token::Match => self.parse_match(start)?,
 
-                        Token::LParenthesis => {
-                            self.tokens.next();
+                Token::True => Spanned::new(Expression::Bool(true), start.clone()),
+                Token::False => Spanned::new(Expression::Bool(false), start.clone()),
+
+                Token::Void => Spanned::new(Expression::Void, start.clone()),
+
+                Token::Int(num) => Spanned::new(Expression::Int(*num), start.clone()),
+                Token::BigInt(num) => Spanned::new(Expression::BigInt(num.clone()), start.clone()),
+                Token::Decimal(num) => Spanned::new(Expression::Decimal(*num), start.clone()),
+                Token::Num(num) => Spanned::new(Expression::Num(*num), start.clone()),
+                Token::Str(string) => Spanned::new(Expression::Str(String::from(string)), start.clone()),
+                Token::InterpolatedStr(parts) => self.parse_interpolated_string(parts, start)?,
+                Token::Id(id) => Spanned::new(Expression::Id(String::from(id)), start.clone()),
+
+                Token::RParenthesis => self.parse_group(start)?,
+
+                Token::If => self.parse_if_expression(start)?,
+                Token::Return => self.parse_return(start)?,
+                Token::Outer => self.parse_outer_assignment(start)?,
+                Token::Break => self.parse_loop_control(Expression::Break, start)?,
+                Token::Continue => self.parse_loop_control(Expression::Continue, start)?,
+
+                _ => return error(ErrorCode::ExpectedExpression, Some(start), format!("Expected expression, got {:?}", token.node)),
+            };
+
+            while let Some(next_token) = self.tokens.peek() {
+                lhs = match &next_token.node {
+                    Token::EOS | Token::RBrace | Token::LBrace | Token::LBracket | Token::LParenthesis | 
+                    Token::Colon | Token::Comma | Token::FatArrow | Token::By | Token::DocComment(_) => {
+                        break;
+                    },
+
+                    Token::Assign => self.parse_assign(lhs, prec)?,
+
+                    // Field access, method calls and indexing bind tighter than
+                    // any operator.
+                    Token::Dot => self.parse_field_access(lhs)?,
+                    Token::RBracket => self.parse_index(lhs)?,
+
+                    Token::DotDot | Token::DotDotEq => {
+                        if prec >= Precedence::Range {
                             break;
                         }
 
-                        Token::Assign => self.parse_assign(lhs, prec)?,
+                        self.parse_range(lhs)?
+                    }
 
-                        Token::Op(op) => {
-                            let nop = match op.to_infix() {
-                                Some(op) => op,
-                                None => {
-                                    return Err(format!("{:?} is not a valid infix operator", op))
-                                }
-                            };
+                    Token::RParenthesis => {
+                        self.next_token();
+                        let args = self.parse_arguments()?;
 
-                            let nop_prec = nop.precedence();
+                        let span = self.span_from(&lhs.span);
+                        Spanned::new(Expression::Call(Box::new(lhs), args), span)
+                    }
 
-                            if prec >= nop_prec {
-                                break;
+                    Token::Op(op) => {
+                        let nop = match op.to_infix() {
+                            Some(op) => op,
+                            None => {
+                                return error(ErrorCode::UnexpectedToken, Some(&next_token.span), format!("{:?} is not a valid infix operator", op))
                             }
+                        };
+
+                        let nop_prec = nop.precedence();
 
-                            self.parse_infix_expression(lhs, nop, nop_prec)?
+                        if prec >= nop_prec {
+                            break;
                         }
 
-                        _ => return Err(format!("Expected operator, got {:?}", next_token)),
+                        // `**` is right-associative, so its right operand can
+                        // itself contain another `**`.
+                        let rhs_prec = match nop {
+                            InfixOperator::Pow => Precedence::Prefix,
+                            _ => nop_prec,
+                        };
+
+                        self.parse_infix_expression(lhs, nop, rhs_prec)?
                     }
-                } else {
-                    break;
+
+                    _ => return error(ErrorCode::UnexpectedToken, Some(&next_token.span), format!("Expected operator, got {:?}", next_token.node)),
                 }
             }
 
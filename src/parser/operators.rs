@@ -3,8 +3,13 @@ use crate::lexer::Operator;
 #[derive(Debug, Clone)]
 pub enum InfixOperator {
     Add, Sub, Mul, Div, 
+
+    Mod, FloorDiv, Pow,
+
+    And, Or,
     
     Equals, 
+    NotEquals,
     
     LessThan, 
     LessThanOrEquals, 
@@ -20,8 +25,16 @@ impl Operator {
             Operator::Sub => Some(InfixOperator::Sub),
             Operator::Mul => Some(InfixOperator::Mul),
             Operator::Div => Some(InfixOperator::Div),
+
+            Operator::Mod => Some(InfixOperator::Mod),
+            Operator::FloorDiv => Some(InfixOperator::FloorDiv),
+            Operator::Pow => Some(InfixOperator::Pow),
+
+            Operator::And => Some(InfixOperator::And),
+            Operator::Or => Some(InfixOperator::Or),
             
             Operator::Equals => Some(InfixOperator::Equals),
+            Operator::NotEquals => Some(InfixOperator::NotEquals),
             Operator::GreaterThan => Some(InfixOperator::GreaterThan),
             Operator::GreaterThanOrEquals => Some(InfixOperator::GreaterThanOrEquals),
             Operator::LessThan => Some(InfixOperator::LessThan),
@@ -43,6 +56,7 @@ impl Operator {
         match self {
             Operator::Add => Some(PrefixOperator::Positive),
             Operator::Sub => Some(PrefixOperator::Negative),
+            Operator::Not => Some(PrefixOperator::Not),
 
             _ => None
         }
@@ -0,0 +1,203 @@
+use crate::{process, report, Sources};
+
+use neesy::error::ErrorCode;
+use neesy::evaluator::{Evaluator, Value};
+use neesy::lexer::{Lexer, Token};
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const PROMPT: &str = "neesy> ";
+const CONTINUATION_PROMPT: &str = "  ...> ";
+
+const HELP: &str = "\
+:help         Show this message
+:quit         Exit the REPL
+:reset        Forget every variable defined so far
+:load <file>  Run a source file in the current session
+:env          List the variables defined so far";
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".neesy_history"))
+}
+
+// Whether `buf` has unclosed braces, brackets, parentheses, strings or block
+// comments, in which case the REPL keeps reading lines before evaluating it.
+fn is_incomplete(buf: &str) -> bool {
+    let mut lexer = Lexer::new(buf.to_string(), "<repl>");
+
+    let tokens = match lexer.collect() {
+        Ok(tokens) => tokens,
+        Err(err) => return matches!(err.code(), ErrorCode::UnterminatedString | ErrorCode::UnterminatedComment),
+    };
+
+    let mut depth = 0;
+
+    for token in &tokens {
+        match token.node {
+            Token::RBrace | Token::HashRBrace | Token::RBracket | Token::RParenthesis => depth += 1,
+            Token::LBrace | Token::LBracket | Token::LParenthesis => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth > 0
+}
+
+enum Command<'a> {
+    Help,
+    Quit,
+    Reset,
+    Load(&'a str),
+    Env,
+}
+
+// Meta-commands share the `:` prefix with array literals, so only lines made
+// of a known command name (and its argument) are treated as commands.
+fn parse_command(line: &str) -> Option<Result<Command<'_>, String>> {
+    let line = line.trim();
+    let rest = line.strip_prefix(':')?;
+
+    let (name, argument) = match rest.find(char::is_whitespace) {
+        Some(index) => (&rest[..index], rest[index..].trim()),
+        None => (rest, ""),
+    };
+
+    let command = match name {
+        "help" => Command::Help,
+        "quit" => Command::Quit,
+        "reset" => Command::Reset,
+        "env" => Command::Env,
+        "load" => {
+            if argument.is_empty() {
+                return Some(Err("Usage: :load <file>".to_string()));
+            }
+
+            return Some(Ok(Command::Load(argument)));
+        }
+
+        _ => return None,
+    };
+
+    if !argument.is_empty() {
+        return Some(Err(format!(":{} takes no arguments", name)));
+    }
+
+    Some(Ok(command))
+}
+
+fn print_values(values: &[Value]) {
+    for value in values {
+        match value {
+            Value::Void => {},
+            Value::Str(s) => println!("{:?}", s),
+            _ => println!("{}", value),
+        }
+    }
+}
+
+pub fn run(builtin_functions: &HashMap<String, Value>) {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            println!("Cannot start the REPL: {}", err);
+            return;
+        }
+    };
+
+    let history = history_path();
+    if let Some(path) = &history {
+        let _ = editor.load_history(path);
+    }
+
+    let mut eval = Evaluator::new(builtin_functions);
+    let mut buf = String::new();
+
+    // Each input gets its own name, `<repl:N>`, for the spans of its code.
+    let mut sources = Sources::new();
+    let mut inputs = 0;
+
+    println!("neesy {}, type :help for help.", env!("CARGO_PKG_VERSION"));
+
+    loop {
+        let prompt = if buf.is_empty() { PROMPT } else { CONTINUATION_PROMPT };
+
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+
+            Err(ReadlineError::Interrupted) => {
+                buf.clear();
+                continue;
+            }
+
+            Err(ReadlineError::Eof) => break,
+
+            Err(err) => {
+                println!("{}", err);
+                break;
+            }
+        };
+
+        if buf.is_empty() {
+            if let Some(command) = parse_command(&line) {
+                let _ = editor.add_history_entry(line.as_str());
+
+                match command {
+                    Ok(Command::Help) => println!("{}", HELP),
+                    Ok(Command::Quit) => break,
+                    Ok(Command::Reset) => eval = Evaluator::new(builtin_functions),
+
+                    Ok(Command::Env) => {
+                        for (name, value) in eval.variables() {
+                            println!("{} = {}", name, value);
+                        }
+                    }
+
+                    Ok(Command::Load(file)) => match std::fs::read_to_string(file) {
+                        Ok(source) => {
+                            let result = process(&source, file, &mut eval);
+                            sources.insert(file.to_string(), source);
+
+                            if let Err(errors) = result {
+                                report(&errors, &sources);
+                            }
+                        }
+
+                        Err(err) => println!("Cannot read {}: {}", file, err),
+                    },
+
+                    Err(message) => println!("{}", message),
+                }
+
+                continue;
+            }
+        }
+
+        buf.push_str(&line);
+        buf.push('\n');
+
+        if is_incomplete(&buf) {
+            continue;
+        }
+
+        let _ = editor.add_history_entry(buf.trim_end());
+
+        inputs += 1;
+        let name = format!("<repl:{}>", inputs);
+
+        let result = process(&buf, &name, &mut eval);
+        sources.insert(name, std::mem::take(&mut buf));
+
+        match result {
+            Ok(values) => print_values(&values),
+            Err(errors) => report(&errors, &sources),
+        }
+    }
+
+    if let Some(path) = &history {
+        let _ = editor.save_history(path);
+    }
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn start() -> Self {
+        Position { line: 1, column: 1 }
+    }
+}
+
+// A range of source text, `start` inclusive and `end` exclusive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub file: Rc<str>,
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(file: Rc<str>, start: Position, end: Position) -> Self {
+        Span { file, start, end }
+    }
+
+    // Returns the span going from the start of `self` to the end of `other`.
+    pub fn to(&self, other: &Span) -> Span {
+        Span::new(self.file.clone(), self.start, other.end)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.start.line, self.start.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Spanned { node, span }
+    }
+}